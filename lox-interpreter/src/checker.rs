@@ -0,0 +1,1315 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
+
+use lox_syntax::{Expr, Literal, MatchArm, Param, Pattern, Stmt, Token, TokenType, TypeAnnotation};
+
+use crate::{
+    class,
+    errors::{Error, ErrorKind},
+    function::Arity,
+};
+
+// Static types for `lox check --types`. Whatever the checker can't tell is `Any`, which fits
+// everywhere: code without annotations goes through and only what is known gets checked.
+#[derive(Clone, Debug)]
+enum Type {
+    Any,
+    Nil,
+    Number,
+    String,
+    Bool,
+    List,
+    // `None` for a function written as the `Function` annotation, whose signature is unknown
+    Function(Option<Rc<Signature>>),
+    // the class itself, calling it makes an instance
+    Class(Rc<ClassType>),
+    Instance(Rc<ClassType>),
+    Nullable(Box<Type>),
+}
+
+#[derive(Clone, Debug)]
+struct Signature {
+    name: String,
+    params: Vec<(String, Type)>,
+    arity: Arity,
+    returns: Type,
+}
+
+// signatures of methods by name
+type Signatures = RefCell<HashMap<String, Rc<Signature>>>;
+
+#[derive(Debug)]
+struct ClassType {
+    name: String,
+    superclass: RefCell<Option<Rc<ClassType>>>,
+    // the superclass and members are filled in after every class of the block is known, so they can refer to
+    // each other
+    fields: RefCell<HashMap<String, Type>>,
+    methods: Signatures,
+    // accessors by name, with the type a getter returns
+    getters: RefCell<HashMap<String, Type>>,
+    static_fields: RefCell<HashMap<String, Type>>,
+    static_methods: Signatures,
+    // composing traits or extending something that isn't a known class adds members the
+    // checker can't see, so unknown properties are `Any` instead of an error
+    open: RefCell<bool>,
+}
+
+impl Type {
+    // whether a value of type `actual` can be stored where `self` is expected
+    fn accepts(&self, actual: &Type) -> bool {
+        match (self, actual) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Nullable(_), Type::Nil) => true,
+            (Type::Nullable(expected), Type::Nullable(actual)) => expected.accepts(actual),
+            (Type::Nullable(expected), actual) => expected.accepts(actual),
+            (Type::Nil, Type::Nil)
+            | (Type::Number, Type::Number)
+            | (Type::String, Type::String)
+            | (Type::Bool, Type::Bool)
+            | (Type::List, Type::List) => true,
+            (Type::Function(_), Type::Function(_) | Type::Class(_)) => true,
+            (Type::Class(expected), Type::Class(actual))
+            | (Type::Instance(expected), Type::Instance(actual)) => actual.is_subclass_of(expected),
+            _ => false,
+        }
+    }
+
+    // the type of a value that is either of the two
+    fn join(self, other: Type) -> Type {
+        if self.accepts(&other) {
+            self
+        } else if other.accepts(&self) {
+            other
+        } else if matches!(self, Type::Nil) {
+            other.nullable()
+        } else if matches!(other, Type::Nil) {
+            self.nullable()
+        } else {
+            Type::Any
+        }
+    }
+
+    fn nullable(self) -> Type {
+        match self {
+            Type::Any | Type::Nil | Type::Nullable(_) => self,
+            _ => Type::Nullable(Box::new(self)),
+        }
+    }
+
+    fn non_nil(self) -> Type {
+        match self {
+            Type::Nullable(inner) => *inner,
+            _ => self,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Type::Number | Type::Any)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Nil => write!(f, "Nil"),
+            Type::Number => write!(f, "Number"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::List => write!(f, "List"),
+            Type::Function(_) => write!(f, "Function"),
+            Type::Class(c) => write!(f, "class {}", c.name),
+            Type::Instance(c) => write!(f, "{}", c.name),
+            Type::Nullable(inner) => write!(f, "{}?", inner),
+        }
+    }
+}
+
+impl ClassType {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            superclass: RefCell::new(None),
+            fields: RefCell::new(HashMap::new()),
+            methods: RefCell::new(HashMap::new()),
+            getters: RefCell::new(HashMap::new()),
+            static_fields: RefCell::new(HashMap::new()),
+            static_methods: RefCell::new(HashMap::new()),
+            open: RefCell::new(false),
+        }
+    }
+
+    fn superclass(&self) -> Option<Rc<ClassType>> {
+        self.superclass.borrow().clone()
+    }
+
+    fn is_subclass_of(&self, other: &ClassType) -> bool {
+        std::ptr::eq(self, other)
+            || self
+                .superclass()
+                .is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    fn is_open(&self) -> bool {
+        *self.open.borrow() || self.superclass().is_some_and(|s| s.is_open())
+    }
+
+    fn field(&self, name: &str) -> Option<Type> {
+        self.fields
+            .borrow()
+            .get(name)
+            .cloned()
+            .or_else(|| self.superclass()?.field(name))
+    }
+
+    fn method(&self, name: &str) -> Option<Rc<Signature>> {
+        self.methods
+            .borrow()
+            .get(name)
+            .cloned()
+            .or_else(|| self.superclass()?.method(name))
+    }
+
+    fn getter(&self, name: &str) -> Option<Type> {
+        self.getters
+            .borrow()
+            .get(name)
+            .cloned()
+            .or_else(|| self.superclass()?.getter(name))
+    }
+
+    fn static_member(&self, name: &str) -> Option<Type> {
+        let method = self.static_methods.borrow().get(name).cloned();
+        method
+            .map(|signature| Type::Function(Some(signature)))
+            .or_else(|| self.static_fields.borrow().get(name).cloned())
+            .or_else(|| self.superclass()?.static_member(name))
+    }
+}
+
+struct Binding {
+    ty: Type,
+    // annotated variables keep their type, the others take whatever is assigned to them
+    annotated: bool,
+}
+
+// The checker behind `lox check --types`. It walks the program once, inferring the types of
+// expressions and variables from literals, annotations and declarations, and collects every
+// mismatch it finds instead of stopping at the first one.
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    // declared return type of the functions being checked, innermost last
+    returns: Vec<(String, Option<Type>)>,
+    // every property assigned somewhere in the program: fields are created by assigning them,
+    // from any method or from outside the class, so reading one of these is never an error
+    assigned: HashSet<String>,
+    errors: Vec<Error>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            returns: Vec::new(),
+            assigned: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, statements: &[Stmt]) -> Vec<Error> {
+        assigned_properties(statements, &mut self.assigned);
+        self.declare_block(statements);
+        for stmt in statements {
+            self.check_stmt(stmt);
+        }
+        std::mem::take(&mut self.errors)
+    }
+
+    fn error(&mut self, token: &Token, message: impl Into<String>) {
+        self.errors
+            .push(Error::new(ErrorKind::TypeError(message.into()), Some(token.clone())));
+    }
+
+    // SCOPES
+
+    fn define(&mut self, name: &Token, ty: Type, annotated: bool) {
+        self.scopes
+            .last_mut()
+            .expect("Scope must exist")
+            .insert(name.to_string(), Binding { ty, annotated });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn scoped(&mut self, check: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        check(self);
+        self.scopes.pop();
+    }
+
+    // Classes and functions of a block can be used before their declaration, by the functions
+    // declared next to them, so their types are known before the statements are checked.
+    fn declare_block(&mut self, statements: &[Stmt]) {
+        let declarations: Vec<&Stmt> = statements
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Export { declaration, .. } => declaration.as_ref(),
+                stmt => stmt,
+            })
+            .collect();
+
+        let mut classes = Vec::new();
+        for stmt in &declarations {
+            if let Stmt::Class { name, .. } = stmt {
+                let class = Rc::new(ClassType::new(name.to_string()));
+                self.define(name, Type::Class(Rc::clone(&class)), true);
+                classes.push((*stmt, class));
+            }
+        }
+
+        // superclasses need every class of the block, members need the superclasses
+        for (stmt, class) in &classes {
+            self.inherit(stmt, class);
+        }
+        for (stmt, class) in &classes {
+            self.declare_members(stmt, class);
+        }
+
+        for stmt in &declarations {
+            if let Stmt::Function {
+                name,
+                params,
+                return_type,
+                ..
+            } = stmt
+            {
+                let signature = self.signature(name, params, return_type);
+                self.define(name, Type::Function(Some(signature)), true);
+            }
+        }
+    }
+
+    fn inherit(&mut self, stmt: &Stmt, class: &Rc<ClassType>) {
+        let Stmt::Class {
+            name,
+            superclass: Some(superclass),
+            ..
+        } = stmt
+        else {
+            return;
+        };
+
+        match self.check_expr(superclass) {
+            // a cycle fails at runtime anyway, when the later class isn't defined yet
+            Type::Class(superclass) if !superclass.is_subclass_of(class) => {
+                *class.superclass.borrow_mut() = Some(superclass);
+            }
+            Type::Class(_) | Type::Any => *class.open.borrow_mut() = true,
+            other => self.error(name, format!("Superclass must be a class, got {}.", other)),
+        }
+    }
+
+    fn declare_members(&mut self, stmt: &Stmt, class: &Rc<ClassType>) {
+        let Stmt::Class {
+            traits,
+            methods,
+            getters,
+            setters,
+            static_methods,
+            static_fields,
+            fields,
+            ..
+        } = stmt
+        else {
+            return;
+        };
+
+        if !traits.is_empty() {
+            *class.open.borrow_mut() = true;
+        }
+
+        for (name, annotation) in fields {
+            let ty = self.annotation_type(annotation);
+            class.fields.borrow_mut().insert(name.to_string(), ty);
+        }
+
+        for method in methods.iter() {
+            if let Stmt::Function {
+                name,
+                params,
+                return_type,
+                ..
+            } = method.as_ref()
+            {
+                let signature = self.signature(name, params, return_type);
+                class.methods.borrow_mut().insert(name.to_string(), signature);
+            }
+        }
+        for getter in getters.iter() {
+            if let Stmt::Function { name, return_type, .. } = getter.as_ref() {
+                let ty = match return_type {
+                    Some(annotation) => self.annotation_type(annotation),
+                    None => Type::Any,
+                };
+                class.getters.borrow_mut().insert(name.to_string(), ty);
+            }
+        }
+        for setter in setters.iter() {
+            if let Stmt::Function { name, .. } = setter.as_ref() {
+                class.getters.borrow_mut().entry(name.to_string()).or_insert(Type::Any);
+            }
+        }
+        for method in static_methods.iter() {
+            if let Stmt::Function {
+                name,
+                params,
+                return_type,
+                ..
+            } = method.as_ref()
+            {
+                let signature = self.signature(name, params, return_type);
+                class.static_methods.borrow_mut().insert(name.to_string(), signature);
+            }
+        }
+        for field in static_fields {
+            if let Stmt::Var {
+                name,
+                initializer,
+                annotation,
+                ..
+            } = field
+            {
+                let ty = self.declared_type(name, initializer.as_ref(), annotation.as_ref());
+                class.static_fields.borrow_mut().insert(name.to_string(), ty);
+            }
+        }
+    }
+
+    fn signature(
+        &mut self,
+        name: &Token,
+        params: &[Param],
+        return_type: &Option<TypeAnnotation>,
+    ) -> Rc<Signature> {
+        let arity = Arity {
+            min: params.iter().filter(|p| p.default.is_none() && !p.rest).count(),
+            max: match params.last() {
+                Some(p) if p.rest => None,
+                _ => Some(params.len()),
+            },
+        };
+        let types: Vec<(String, Type)> = params
+            .iter()
+            .map(|param| {
+                let ty = match (&param.annotation, param.rest) {
+                    (_, true) => Type::List,
+                    (Some(annotation), false) => self.annotation_type(annotation),
+                    (None, false) => Type::Any,
+                };
+                (param.name.to_string(), ty)
+            })
+            .collect();
+        let returns = match return_type {
+            Some(annotation) => self.annotation_type(annotation),
+            None => Type::Any,
+        };
+
+        Rc::new(Signature {
+            name: name.to_string(),
+            params: types,
+            arity,
+            returns,
+        })
+    }
+
+    fn annotation_type(&mut self, annotation: &TypeAnnotation) -> Type {
+        let name = annotation.name.to_string();
+        let ty = match name.as_str() {
+            "Any" => Type::Any,
+            "Nil" => Type::Nil,
+            "Number" => Type::Number,
+            "String" => Type::String,
+            "Bool" => Type::Bool,
+            "List" => Type::List,
+            "Function" => Type::Function(None),
+            _ => match self.lookup(&name).map(|binding| &binding.ty) {
+                Some(Type::Class(class)) => Type::Instance(Rc::clone(class)),
+                Some(Type::Any) => Type::Any,
+                _ => {
+                    self.error(&annotation.name, format!("Unknown type '{}'.", name));
+                    Type::Any
+                }
+            },
+        };
+
+        match annotation.nullable {
+            true => ty.nullable(),
+            false => ty,
+        }
+    }
+
+    // the annotation when there is one, else the type of the initializer
+    fn declared_type(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+        annotation: Option<&TypeAnnotation>,
+    ) -> Type {
+        let value = initializer.map(|initializer| self.check_expr(initializer));
+        let Some(annotation) = annotation else {
+            return match value {
+                Some(Type::Nil) | None => Type::Any,
+                Some(ty) => ty,
+            };
+        };
+
+        let declared = self.annotation_type(annotation);
+        if let Some(value) = value
+            && !declared.accepts(&value)
+        {
+            self.error(name, format!("Can't assign {} to '{}' of type {}.", value, name, declared));
+        }
+        declared
+    }
+
+    // STATEMENTS
+
+    fn check_stmts(&mut self, statements: &[Stmt]) {
+        self.declare_block(statements);
+        for stmt in statements {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.check_expr(expression);
+            }
+            Stmt::Var {
+                name,
+                initializer,
+                annotation,
+                ..
+            } => {
+                let ty = self.declared_type(name, initializer.as_ref(), annotation.as_ref());
+                self.define(name, ty, annotation.is_some());
+            }
+            Stmt::VarPattern {
+                pattern,
+                initializer,
+                ..
+            } => {
+                self.check_expr(initializer);
+                for name in pattern.bindings() {
+                    self.define(name, Type::Any, false);
+                }
+            }
+            Stmt::Block { statements } => self.scoped(|checker| checker.check_stmts(statements)),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_expr(condition);
+                let narrowed = nil_check(condition);
+                let narrowed_if = |is_true| narrowed.filter(|(_, when)| *when == is_true).map(|(name, _)| name);
+                self.check_narrowed(then_branch, narrowed_if(true));
+                if let Some(else_branch) = else_branch {
+                    self.check_narrowed(else_branch, narrowed_if(false));
+                }
+                // `if (x == nil) return;` leaves `x` not nil for the rest of the block
+                if else_branch.is_none()
+                    && then_branch.always_returns()
+                    && let Some(name) = narrowed_if(false)
+                    && let Some(ty) = self.non_nil(name)
+                {
+                    self.define(name, ty, false);
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.check_expr(condition);
+                self.check_stmt(body);
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                let ty = self.check_expr(iterable);
+                if !matches!(ty, Type::List | Type::Any) {
+                    self.error(name, format!("Can only iterate over lists and generators, got {}.", ty));
+                }
+                self.scoped(|checker| {
+                    checker.define(name, Type::Any, false);
+                    checker.check_stmt(body);
+                });
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                let Some(Binding {
+                    ty: Type::Function(Some(signature)),
+                    ..
+                }) = self.lookup(&name.to_string())
+                else {
+                    return;
+                };
+                let signature = Rc::clone(signature);
+                self.check_function(params, body, &signature, None);
+            }
+            Stmt::Return { keyword, value } => {
+                let ty = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::Nil,
+                };
+                if let Some((function, Some(expected))) = self.returns.last()
+                    && !expected.accepts(&ty)
+                {
+                    let message = format!("Can't return {} from '{}', expected {}.", ty, function, expected);
+                    self.error(keyword, message);
+                }
+            }
+            Stmt::Yield { value, .. } => {
+                if let Some(value) = value {
+                    self.check_expr(value);
+                }
+            }
+            Stmt::Class { name, .. } => self.check_class(name, stmt),
+            Stmt::Export { declaration, .. } => self.check_stmt(declaration),
+            Stmt::Import { alias, names, .. } => {
+                for name in alias.iter().chain(names) {
+                    self.define(name, Type::Any, false);
+                }
+            }
+            Stmt::Enum { name, .. } | Stmt::Trait { name, .. } => self.define(name, Type::Any, true),
+            Stmt::Break => {}
+        }
+    }
+
+    // the type of `name` without nil, when it was declared as `T?`
+    fn non_nil(&self, name: &Token) -> Option<Type> {
+        match self.lookup(&name.to_string()) {
+            Some(Binding {
+                ty: Type::Nullable(inner),
+                ..
+            }) => Some(inner.as_ref().clone()),
+            _ => None,
+        }
+    }
+
+    // Checks `stmt` knowing that `narrowed` isn't nil. The narrowed variable isn't annotated, so
+    // the branch can still assign nil to it.
+    fn check_narrowed(&mut self, stmt: &Stmt, narrowed: Option<&Token>) {
+        match narrowed.and_then(|name| Some((name, self.non_nil(name)?))) {
+            Some((name, ty)) => self.scoped(|checker| {
+                checker.define(name, ty, false);
+                checker.check_stmt(stmt);
+            }),
+            None => self.check_stmt(stmt),
+        }
+    }
+
+    // `this` is an instance for methods and the class for static methods
+    fn check_function(&mut self, params: &[Param], body: &[Stmt], signature: &Signature, this: Option<Type>) {
+        self.returns.push((signature.name.clone(), match signature.returns {
+            Type::Any => None,
+            ref returns => Some(returns.clone()),
+        }));
+        self.scoped(|checker| {
+            if let Some(this) = this {
+                checker.scopes.last_mut().expect("Scope must exist").insert(
+                    "this".to_string(),
+                    Binding {
+                        ty: this,
+                        annotated: true,
+                    },
+                );
+            }
+            for (param, (_, ty)) in params.iter().zip(&signature.params) {
+                if let Some(default) = &param.default {
+                    let value = checker.check_expr(default);
+                    if !ty.accepts(&value) {
+                        let message = format!("Can't use {} as the default of '{}' of type {}.", value, param.name, ty);
+                        checker.error(&param.name, message);
+                    }
+                }
+                checker.define(&param.name, ty.clone(), param.annotation.is_some());
+            }
+            checker.check_stmts(body);
+        });
+        self.returns.pop();
+    }
+
+    fn check_class(&mut self, name: &Token, stmt: &Stmt) {
+        let Stmt::Class {
+            methods,
+            getters,
+            setters,
+            static_methods,
+            ..
+        } = stmt
+        else {
+            return;
+        };
+        let Some(Binding {
+            ty: Type::Class(class),
+            ..
+        }) = self.lookup(&name.to_string())
+        else {
+            return;
+        };
+        let class = Rc::clone(class);
+
+        let instance = Type::Instance(Rc::clone(&class));
+        let statics = Type::Class(Rc::clone(&class));
+        let all: Vec<(&Box<Stmt>, &Type, Option<&Signatures>)> = methods
+            .iter()
+            .map(|method| (method, &instance, Some(&class.methods)))
+            .chain(static_methods.iter().map(|method| (method, &statics, Some(&class.static_methods))))
+            .chain(setters.iter().map(|method| (method, &instance, None)))
+            .collect();
+        for (method, this, declared) in all {
+            let Stmt::Function {
+                name,
+                params,
+                body,
+                return_type,
+            } = method.as_ref()
+            else {
+                continue;
+            };
+            // methods reuse the signature declared with the class, so annotation errors are
+            // reported once
+            let declared = declared.and_then(|declared| declared.borrow().get(&name.to_string()).cloned());
+            let signature = declared.unwrap_or_else(|| self.signature(name, params, return_type));
+            let mut signature = Rc::unwrap_or_clone(signature);
+            // an initializer gives back the instance, whatever its body returns
+            if name.to_string() == "init" {
+                signature.returns = Type::Any;
+            }
+            self.check_function(params, body, &signature, Some(this.clone()));
+        }
+        // the type a getter returns was read with the class, like method signatures
+        for getter in getters {
+            let Stmt::Function { name, params, body, .. } = getter.as_ref() else {
+                continue;
+            };
+            let mut signature = Rc::unwrap_or_clone(self.signature(name, params, &None));
+            signature.returns = class.getter(&name.to_string()).unwrap_or(Type::Any);
+            self.check_function(params, body, &signature, Some(instance.clone()));
+        }
+    }
+
+    // EXPRESSIONS
+
+    fn check_expr(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal { value } => match value {
+                Literal::Number(_) => Type::Number,
+                Literal::String(_) => Type::String,
+                Literal::Bool(_) => Type::Bool,
+                Literal::Null => Type::Nil,
+            },
+            Expr::Grouping { expression } => self.check_expr(expression),
+            Expr::Variable { name } => self
+                .lookup(&name.to_string())
+                .map_or(Type::Any, |binding| binding.ty.clone()),
+            Expr::This { .. } => self
+                .lookup("this")
+                .map_or(Type::Any, |binding| binding.ty.clone()),
+            Expr::Super { method, .. } => {
+                let superclass = match self.lookup("this").map(|binding| &binding.ty) {
+                    Some(Type::Instance(class)) => class.superclass(),
+                    _ => None,
+                };
+                superclass
+                    .and_then(|superclass| superclass.method(&method.to_string()))
+                    .map_or(Type::Any, |signature| Type::Function(Some(signature)))
+            }
+            Expr::Assign { name, value } => {
+                let value = self.check_expr(value);
+                self.assign(name, value.clone());
+                value
+            }
+            Expr::AssignPattern { value, .. } => self.check_expr(value),
+            Expr::Postfix { target, operator } => match target.as_ref() {
+                Expr::Get { object, name, .. } => {
+                    let object = self.check_expr(object);
+                    let field = self.property(&object, name);
+                    let value = self.binary(&field, operator, &Type::Number);
+                    if !field.accepts(&value) {
+                        let message = format!("Can't assign {} to field '{}' of type {}.", value, name, field);
+                        self.error(name, message);
+                    }
+                    field
+                }
+                target => {
+                    let current = self.check_expr(target);
+                    let value = self.binary(&current, operator, &Type::Number);
+                    if let Expr::Variable { name } = target {
+                        self.assign(name, value);
+                    }
+                    current
+                }
+            },
+            Expr::Unary { operator, right } => {
+                let right = self.check_expr(right);
+                if operator.token_type == TokenType::BANG {
+                    return Type::Bool;
+                }
+                if !right.is_number() {
+                    let message = format!("Operand of '{}' must be a number, got {}.", operator.token_type, right);
+                    self.error(operator, message);
+                }
+                Type::Number
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                self.binary(&left, operator, &right)
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                match operator.token_type {
+                    TokenType::QUESTION_QUESTION => left.non_nil().join(right),
+                    _ => left.join(right),
+                }
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_expr(condition);
+                let then_type = self.check_expr(then_branch);
+                let else_type = self.check_expr(else_branch);
+                then_type.join(else_type)
+            }
+            Expr::Comma { left, right } => {
+                self.check_expr(left);
+                self.check_expr(right)
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.check_expr(element);
+                }
+                Type::List
+            }
+            Expr::Lambda { params, body } => {
+                let name = Token {
+                    token_type: TokenType::IDENTIFIER,
+                    literal: Some(Literal::String("lambda".to_string())),
+                    line: 0,
+                    id: 0,
+                };
+                let signature = self.signature(&name, params, &None);
+                self.check_function(params, body, &signature, None);
+                Type::Function(Some(signature))
+            }
+            Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } => match self.check_chain(expr) {
+                (Some(ty), false) => ty,
+                (Some(ty), true) => ty.nullable(),
+                (None, _) => Type::Nil,
+            },
+            Expr::MethodRef { object, name } => match self.check_expr(object) {
+                Type::Instance(class) => class
+                    .method(&name.to_string())
+                    .map_or(Type::Any, |signature| Type::Function(Some(signature))),
+                Type::Class(class) => class.static_member(&name.to_string()).unwrap_or(Type::Any),
+                _ => Type::Any,
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+                operator,
+            } => {
+                let object = self.check_expr(object);
+                let value = self.check_expr(value);
+                let field = self.property(&object, name);
+                let value = match operator {
+                    Some(operator) => self.binary(&field, operator, &value),
+                    None => value,
+                };
+                if !field.accepts(&value) {
+                    let message = format!("Can't assign {} to field '{}' of type {}.", value, name, field);
+                    self.error(name, message);
+                }
+                value
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.check_expr(subject);
+                arms.iter()
+                    .map(|arm| self.check_arm(arm))
+                    .reduce(Type::join)
+                    .unwrap_or(Type::Nil)
+            }
+        }
+    }
+
+    fn check_arm(&mut self, arm: &MatchArm) -> Type {
+        let mut ty = Type::Nil;
+        self.scoped(|checker| {
+            checker.check_pattern(&arm.pattern);
+            for name in arm.pattern.bindings() {
+                checker.define(name, Type::Any, false);
+            }
+            if let Some(guard) = &arm.guard {
+                checker.check_expr(guard);
+            }
+            ty = match arm.body.as_ref() {
+                Stmt::Expression { expression } => checker.check_expr(expression),
+                body => {
+                    checker.check_stmt(body);
+                    Type::Nil
+                }
+            };
+        });
+        ty
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::List { elements, .. } => {
+                for element in elements {
+                    self.check_pattern(element);
+                }
+            }
+            Pattern::Instance { class, fields, .. } => {
+                self.check_expr(class);
+                for field in fields {
+                    self.check_pattern(field);
+                }
+            }
+            Pattern::Constant { value } => {
+                self.check_expr(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn assign(&mut self, name: &Token, value: Type) {
+        let key = name.to_string();
+        let Some(binding) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&key))
+        else {
+            return;
+        };
+
+        if binding.ty.accepts(&value) {
+            return;
+        }
+        if !binding.annotated {
+            // a variable without annotation can hold anything that is assigned to it
+            binding.ty = Type::Any;
+            return;
+        }
+        let message = format!("Can't assign {} to '{}' of type {}.", value, name, binding.ty);
+        self.error(name, message);
+    }
+
+    fn binary(&mut self, left: &Type, operator: &Token, right: &Type) -> Type {
+        // an instance on the left overloads the operator with a method
+        if let Type::Instance(class) = left
+            && let Some(name) = class::operator_method(&operator.token_type)
+        {
+            if let Some(signature) = class.method(name) {
+                return self.call(&Type::Function(Some(signature)), operator, std::slice::from_ref(right), &[]);
+            }
+            if class.is_open() {
+                return Type::Any;
+            }
+        }
+        // else one on the right, called with the left operand
+        if let Type::Instance(class) = right
+            && let Some(name) = class::reflected_method(&operator.token_type)
+        {
+            if let Some(signature) = class.method(name) {
+                return self.call(&Type::Function(Some(signature)), operator, std::slice::from_ref(left), &[]);
+            }
+            if class.is_open() {
+                return Type::Any;
+            }
+        }
+
+        match operator.token_type {
+            TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => Type::Bool,
+            TokenType::PLUS => match (left, right) {
+                (Type::Number, Type::Number) => Type::Number,
+                (Type::String, Type::String) => Type::String,
+                (Type::Any, Type::Number | Type::String) => right.clone(),
+                (Type::Number | Type::String, Type::Any) => left.clone(),
+                (Type::Any, Type::Any) => Type::Any,
+                _ => {
+                    let message = format!(
+                        "Operands of '+' must be two numbers or two strings, got {} and {}.",
+                        left, right
+                    );
+                    self.error(operator, message);
+                    Type::Any
+                }
+            },
+            _ => {
+                if !left.is_number() || !right.is_number() {
+                    let message = format!(
+                        "Operands of '{}' must be numbers, got {} and {}.",
+                        operator.token_type, left, right
+                    );
+                    self.error(operator, message);
+                }
+                match operator.token_type {
+                    TokenType::GREATER
+                    | TokenType::GREATER_EQUAL
+                    | TokenType::LESS
+                    | TokenType::LESS_EQUAL => Type::Bool,
+                    _ => Type::Number,
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, callee: &Type, paren: &Token, arguments: &[Type], named: &[(&Token, Type)]) -> Type {
+        // an instance is called through its `__call__` method
+        if let Type::Instance(class) = callee {
+            match class.method("__call__") {
+                Some(signature) => return self.call(&Type::Function(Some(signature)), paren, arguments, named),
+                None if class.is_open() => return Type::Any,
+                None => {}
+            }
+        }
+
+        let (signature, returns) = match callee {
+            Type::Any | Type::Function(None) => return Type::Any,
+            Type::Function(Some(signature)) => (Some(Rc::clone(signature)), signature.returns.clone()),
+            Type::Class(class) => (class.method("init"), Type::Instance(Rc::clone(class))),
+            _ => {
+                self.error(paren, format!("Can't call a value of type {}.", callee));
+                return Type::Any;
+            }
+        };
+        let Some(signature) = signature else {
+            // a class without initializer
+            if !arguments.is_empty() || !named.is_empty() {
+                let message = format!("Expected 0 arguments but got {}.", arguments.len() + named.len());
+                self.error(paren, message);
+            }
+            return returns;
+        };
+
+        let count = arguments.len() + named.len();
+        if !signature.arity.accepts(count) {
+            let message = format!("Expected {} arguments but got {}.", signature.arity, count);
+            self.error(paren, message);
+            return returns;
+        }
+
+        // the arguments collected by a rest parameter aren't checked one by one
+        let checked = match signature.arity.max {
+            Some(_) => signature.params.len(),
+            None => signature.params.len() - 1,
+        };
+        let positional = signature.params[..checked].iter().zip(arguments);
+        let by_name = named.iter().filter_map(|(name, argument)| {
+            let key = name.to_string();
+            signature
+                .params
+                .iter()
+                .find(|(param, _)| *param == key)
+                .map(|param| (param, argument))
+        });
+        for ((param, expected), argument) in positional.chain(by_name) {
+            if !expected.accepts(argument) {
+                let message = format!(
+                    "Expected {} for parameter '{}' of '{}', got {}.",
+                    expected, param, signature.name, argument
+                );
+                self.error(paren, message);
+            }
+        }
+        returns
+    }
+
+    // The type a chain of property accesses, calls and indexing reaches, and whether a `?.` in it
+    // can skip the rest. `None` when a `?.` is always applied to nil.
+    fn check_chain(&mut self, expr: &Expr) -> (Option<Type>, bool) {
+        let object = match expr {
+            Expr::Get { object, .. } | Expr::Index { object, .. } => object,
+            Expr::Call { callee, .. } => callee,
+            _ => return (Some(self.check_expr(expr)), false),
+        };
+        let (object, mut optional) = self.check_chain(object);
+        let Some(mut object) = object else {
+            return (None, true);
+        };
+        if let Expr::Get { optional: true, .. } = expr {
+            match object {
+                Type::Nil => return (None, true),
+                Type::Nullable(inner) => {
+                    object = *inner;
+                    optional = true;
+                }
+                _ => {}
+            }
+        }
+        (Some(self.check_link(expr, object)), optional)
+    }
+
+    fn check_link(&mut self, expr: &Expr, object: Type) -> Type {
+        match expr {
+            Expr::Call {
+                paren,
+                arguments,
+                named_arguments,
+                ..
+            } => {
+                let arguments: Vec<Type> = arguments.iter().map(|a| self.check_expr(a)).collect();
+                let named: Vec<(&Token, Type)> = named_arguments
+                    .iter()
+                    .map(|(name, a)| (name, self.check_expr(a)))
+                    .collect();
+                self.call(&object, paren, &arguments, &named)
+            }
+            Expr::Get { name, .. } => self.property(&object, name),
+            Expr::Index { bracket, index, .. } => {
+                let index = self.check_expr(index);
+                match &object {
+                    Type::Any => Type::Any,
+                    Type::List => {
+                        if !index.is_number() {
+                            self.error(bracket, format!("List index must be a number, got {}.", index));
+                        }
+                        Type::Any
+                    }
+                    Type::Instance(class) => match class.method("__index__") {
+                        Some(signature) => self.call(&Type::Function(Some(signature)), bracket, &[index], &[]),
+                        None => {
+                            if !class.is_open() {
+                                self.error(bracket, format!("Undefined method '__index__' on {}.", class.name));
+                            }
+                            Type::Any
+                        }
+                    },
+                    _ => {
+                        self.error(bracket, format!("Can't index a value of type {}.", object));
+                        Type::Any
+                    }
+                }
+            }
+            _ => unreachable!("not part of a chain"),
+        }
+    }
+
+    fn property(&mut self, object: &Type, name: &Token) -> Type {
+        let key = name.to_string();
+        match object {
+            Type::Any => Type::Any,
+            Type::Instance(class) => {
+                if let Some(ty) = class.field(&key) {
+                    return ty;
+                }
+                if let Some(signature) = class.method(&key) {
+                    return Type::Function(Some(signature));
+                }
+                if let Some(ty) = class.getter(&key) {
+                    return ty;
+                }
+                if !class.is_open() && !self.assigned.contains(&key) {
+                    self.error(name, format!("Undefined property '{}' on {}.", key, class.name));
+                }
+                Type::Any
+            }
+            Type::Class(class) => match class.static_member(&key) {
+                Some(ty) => ty,
+                None => {
+                    if !class.is_open() && !self.assigned.contains(&key) {
+                        self.error(name, format!("Undefined property '{}' on class {}.", key, class.name));
+                    }
+                    Type::Any
+                }
+            },
+            Type::Nullable(_) | Type::Nil => {
+                let message = format!("Can't read '{}' from {}, it can be nil. Use '?.' instead.", key, object);
+                self.error(name, message);
+                Type::Any
+            }
+            _ => {
+                self.error(name, format!("Only instances have properties, got {}.", object));
+                Type::Any
+            }
+        }
+    }
+}
+
+// The variable `condition` compares with nil, and whether it isn't nil when `condition` is
+// true: `x != nil` and `nil != x` narrow it where they are true, `x == nil` where it is false.
+fn nil_check(condition: &Expr) -> Option<(&Token, bool)> {
+    let Expr::Binary { left, operator, right } = condition else {
+        return None;
+    };
+    let when = match operator.token_type {
+        TokenType::BANG_EQUAL => true,
+        TokenType::EQUAL_EQUAL => false,
+        _ => return None,
+    };
+    match (left.as_ref(), right.as_ref()) {
+        (Expr::Variable { name }, Expr::Literal { value: Literal::Null })
+        | (Expr::Literal { value: Literal::Null }, Expr::Variable { name }) => Some((name, when)),
+        _ => None,
+    }
+}
+
+// names of the properties assigned with `object.name = ...` anywhere in `statements`
+fn assigned_properties(statements: &[Stmt], fields: &mut HashSet<String>) {
+    for stmt in statements {
+        assigned_stmt(stmt, fields);
+    }
+}
+
+fn assigned_stmt(stmt: &Stmt, fields: &mut HashSet<String>) {
+    match stmt {
+        Stmt::Expression { expression } | Stmt::Print { expression } => assigned_expr(expression, fields),
+        Stmt::Var {
+            initializer: Some(expression),
+            ..
+        }
+        | Stmt::VarPattern {
+            initializer: expression,
+            ..
+        }
+        | Stmt::Return {
+            value: Some(expression),
+            ..
+        }
+        | Stmt::Yield {
+            value: Some(expression),
+            ..
+        } => assigned_expr(expression, fields),
+        Stmt::Block { statements } | Stmt::Function { body: statements, .. } => assigned_properties(statements, fields),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            assigned_expr(condition, fields);
+            assigned_stmt(then_branch, fields);
+            if let Some(else_branch) = else_branch {
+                assigned_stmt(else_branch, fields);
+            }
+        }
+        Stmt::While { condition, body } => {
+            assigned_expr(condition, fields);
+            assigned_stmt(body, fields);
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            assigned_expr(iterable, fields);
+            assigned_stmt(body, fields);
+        }
+        Stmt::Class {
+            methods,
+            getters,
+            setters,
+            static_methods,
+            static_fields,
+            ..
+        } => {
+            for method in methods.iter().chain(getters).chain(setters).chain(static_methods) {
+                assigned_stmt(method, fields);
+            }
+            assigned_properties(static_fields, fields);
+        }
+        Stmt::Trait { methods, .. } => {
+            for method in methods {
+                assigned_stmt(method, fields);
+            }
+        }
+        Stmt::Export { declaration, .. } => assigned_stmt(declaration, fields),
+        _ => {}
+    }
+}
+
+fn assigned_expr(expr: &Expr, fields: &mut HashSet<String>) {
+    match expr {
+        Expr::Set { object, name, value, .. } => {
+            fields.insert(name.to_string());
+            assigned_expr(object, fields);
+            assigned_expr(value, fields);
+        }
+        Expr::Assign { value, .. } | Expr::AssignPattern { value, .. } => assigned_expr(value, fields),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } | Expr::Comma { left, right } => {
+            assigned_expr(left, fields);
+            assigned_expr(right, fields);
+        }
+        Expr::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            assigned_expr(condition, fields);
+            assigned_expr(then_branch, fields);
+            assigned_expr(else_branch, fields);
+        }
+        Expr::Call {
+            callee,
+            arguments,
+            named_arguments,
+            ..
+        } => {
+            assigned_expr(callee, fields);
+            for argument in arguments.iter().chain(named_arguments.iter().map(|(_, a)| a)) {
+                assigned_expr(argument, fields);
+            }
+        }
+        Expr::Grouping { expression } | Expr::Unary { right: expression, .. } => assigned_expr(expression, fields),
+        Expr::Postfix { target, .. } => match target.as_ref() {
+            Expr::Get { object, name, .. } => {
+                fields.insert(name.to_string());
+                assigned_expr(object, fields);
+            }
+            target => assigned_expr(target, fields),
+        },
+        Expr::Get { object, .. } | Expr::MethodRef { object, .. } => assigned_expr(object, fields),
+        Expr::Index { object, index, .. } => {
+            assigned_expr(object, fields);
+            assigned_expr(index, fields);
+        }
+        Expr::List { elements, .. } => {
+            for element in elements {
+                assigned_expr(element, fields);
+            }
+        }
+        Expr::Lambda { body, .. } => assigned_properties(body, fields),
+        Expr::Match { subject, arms, .. } => {
+            assigned_expr(subject, fields);
+            for arm in arms {
+                assigned_stmt(&arm.body, fields);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,23 +1,77 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
-use lox_syntax::Token;
+use lox_syntax::{Token, TokenType};
 
 use crate::{
     errors::{Error, ResultExec},
-    function::Function,
+    function::{Arity, Function},
+    gc::{self, Collect, Trace, Tracer},
     interpreter::LoxCallable,
     Value,
 };
 
+// The method that overloads `operator` for instances on its left. `==` and `!=` call `__eq__`,
+// they are compared by `Interpreter::is_equal`.
+pub(crate) fn operator_method(operator: &TokenType) -> Option<&'static str> {
+    match operator {
+        TokenType::PLUS => Some("__add__"),
+        TokenType::MINUS => Some("__sub__"),
+        TokenType::STAR => Some("__mul__"),
+        TokenType::SLASH => Some("__div__"),
+        TokenType::PERCENT => Some("__mod__"),
+        TokenType::LESS => Some("__lt__"),
+        TokenType::LESS_EQUAL => Some("__le__"),
+        TokenType::GREATER => Some("__gt__"),
+        TokenType::GREATER_EQUAL => Some("__ge__"),
+        _ => None,
+    }
+}
+
+// The method of an instance on the right called, with the left operand, when the left one
+// doesn't overload `operator`: `1 + v` calls `v.__radd__(1)` and `a > b` calls `b.__lt__(a)`.
+pub(crate) fn reflected_method(operator: &TokenType) -> Option<&'static str> {
+    match operator {
+        TokenType::PLUS => Some("__radd__"),
+        TokenType::MINUS => Some("__rsub__"),
+        TokenType::STAR => Some("__rmul__"),
+        TokenType::SLASH => Some("__rdiv__"),
+        TokenType::PERCENT => Some("__rmod__"),
+        TokenType::LESS => Some("__gt__"),
+        TokenType::LESS_EQUAL => Some("__ge__"),
+        TokenType::GREATER => Some("__lt__"),
+        TokenType::GREATER_EQUAL => Some("__le__"),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Class {
     name: String,
     superclass: Option<Rc<Class>>,
     methods: HashMap<String, Function>,
+    getters: HashMap<String, Function>,
+    setters: HashMap<String, Function>,
+    // the class acts as its own metaclass: static methods and fields live here
+    static_methods: HashMap<String, Function>,
+    fields: Rc<RefCell<HashMap<String, Value>>>,
 }
 
 impl Class {
-    pub fn new(name: String, superclass_value: Option<Value>, methods: HashMap<String, Function>) -> Self {
+    pub fn new(
+        name: String,
+        superclass_value: Option<Value>,
+        methods: HashMap<String, Function>,
+        getters: HashMap<String, Function>,
+        setters: HashMap<String, Function>,
+        static_methods: HashMap<String, Function>,
+    ) -> Self {
         let mut superclass = None;
         if let Some(unwraped) = superclass_value {
             match unwraped {
@@ -25,7 +79,15 @@ impl Class {
                 _ => panic!("Expected a class as superclass."),
             }
         }
-        Self { name, superclass, methods }
+        Self {
+            name,
+            superclass,
+            methods,
+            getters,
+            setters,
+            static_methods,
+            fields: gc::alloc(HashMap::new()),
+        }
     }
 
     pub fn find_method(&self, name: &str) -> Option<Function> {
@@ -33,32 +95,157 @@ impl Class {
             self.superclass.as_ref()?.find_method(name)
         })
     }
+
+    pub fn find_getter(&self, name: &str) -> Option<Function> {
+        self.getters.get(name).cloned().or_else(|| {
+            self.superclass.as_ref()?.find_getter(name)
+        })
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<Function> {
+        self.setters.get(name).cloned().or_else(|| {
+            self.superclass.as_ref()?.find_setter(name)
+        })
+    }
+
+    pub fn find_static_method(&self, name: &str) -> Option<Function> {
+        self.static_methods.get(name).cloned().or_else(|| {
+            self.superclass.as_ref()?.find_static_method(name)
+        })
+    }
+
+    fn find_field(&self, name: &str) -> Option<Value> {
+        self.fields.borrow().get(name).cloned().or_else(|| {
+            self.superclass.as_ref()?.find_field(name)
+        })
+    }
+
+    // Property access on the class itself: static fields first, then static methods bound to
+    // `klass`, both inherited from superclasses.
+    pub fn get(klass: &Rc<Class>, name: &Token) -> ResultExec<Value> {
+        let key = name.to_string();
+        klass
+            .find_field(&key)
+            .or_else(|| Class::static_method(klass, &key).map(Value::Callable))
+            .ok_or_else(|| {
+                Error::undefined_var(format!("Undefined property '{}'.", key), Some(name.clone()))
+            })
+    }
+
+    // a static method bound to `klass`
+    pub fn static_method(klass: &Rc<Class>, name: &str) -> Option<Function> {
+        klass
+            .find_static_method(name)?
+            .bind(Value::Class(Rc::clone(klass)))
+    }
+
+    // Instances hold their own copy of the class, but the copies share the static fields map, so
+    // it tells whether two classes are the same one.
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    // the fields a `Point(x, y)` pattern matches, named after the initializer parameters
+    pub fn init_params(&self) -> Vec<String> {
+        self.find_method("init")
+            .map(|init| init.param_names())
+            .unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set(&self, name: &Token, value: &Value) {
+        self.fields
+            .borrow_mut()
+            .insert(name.to_string(), value.clone());
+    }
 }
 
-impl LoxCallable for Class {
-    fn call(
+impl Class {
+    fn instantiate(
         &self,
         interpreter: &mut crate::Interpreter,
-        arguments: &Vec<crate::Value>,
-    ) -> crate::errors::ResultExec<crate::Value> {
-        let instance = Rc::new(RefCell::new(Instance::new(Rc::new(self.clone()))));
+        paren: Option<&Token>,
+        arguments: &[Value],
+        named: &[(Token, Value)],
+    ) -> ResultExec<Value> {
+        let instance = gc::alloc(Instance::new(Rc::new(self.clone())));
 
         if let Some(init) = self.find_method("init")
-            && let Some(binded) = init.bind(Rc::clone(&instance))
+            && let Some(binded) = init.bind(Value::Instance(Rc::clone(&instance)))
         {
-            binded.call(interpreter, arguments)?;
+            binded.invoke(interpreter, paren, arguments, named)?;
+        } else if let Some((name, _)) = named.first() {
+            return Err(Error::wrong_arity(
+                format!("Unexpected argument '{}'.", name),
+                Some(name.clone()),
+            ));
         }
 
-        return Ok(Value::Instance(Rc::clone(&instance)));
+        Ok(Value::Instance(Rc::clone(&instance)))
+    }
+}
+
+impl LoxCallable for Class {
+    fn call(
+        &self,
+        interpreter: &mut crate::Interpreter,
+        arguments: &Vec<crate::Value>,
+    ) -> crate::errors::ResultExec<crate::Value> {
+        self.instantiate(interpreter, None, arguments, &[])
+    }
+
+    fn call_named(
+        &self,
+        interpreter: &mut crate::Interpreter,
+        paren: &Token,
+        arguments: &Vec<crate::Value>,
+        named: &[(Token, crate::Value)],
+    ) -> crate::errors::ResultExec<crate::Value> {
+        self.instantiate(interpreter, Some(paren), arguments, named)
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         let initializer = self.find_method("init");
         if let Some(init) = initializer {
             return init.arity();
         } 
 
-        0
+        Arity::exact(0)
+    }
+}
+
+// copies of a class share its static fields, like in `is_subclass_of`
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
+}
+
+impl Eq for Class {}
+
+impl Hash for Class {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.fields).hash(state);
+    }
+}
+
+impl Trace for Class {
+    fn trace(&self, tracer: &mut Tracer) {
+        let functions = [&self.methods, &self.getters, &self.setters, &self.static_methods];
+        for function in functions.into_iter().flat_map(HashMap::values) {
+            function.trace(tracer);
+        }
+        if let Some(superclass) = &self.superclass {
+            tracer.shared(superclass);
+        }
+        tracer.edge(&self.fields);
     }
 }
 
@@ -68,41 +255,149 @@ impl Display for Class {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Trait {
+    name: String,
+    methods: HashMap<String, Function>,
+}
+
+impl Trait {
+    pub fn new(name: String, methods: HashMap<String, Function>) -> Self {
+        Self { name, methods }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn methods(&self) -> &HashMap<String, Function> {
+        &self.methods
+    }
+}
+
+impl Trace for Trait {
+    fn trace(&self, tracer: &mut Tracer) {
+        for method in self.methods.values() {
+            method.trace(tracer);
+        }
+    }
+}
+
+impl Display for Trait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Instance {
     klass: Rc<Class>,
     fields: Rc<RefCell<HashMap<String, Value>>>,
+    // the Rust value behind an instance of a class registered from Rust, a `RefCell<T>`
+    data: Rc<RefCell<Option<Rc<dyn Any>>>>,
 }
 
 impl Instance {
     pub fn new(klass: Rc<Class>) -> Self {
+        Self::with_fields(klass, HashMap::new())
+    }
+
+    pub fn with_fields(klass: Rc<Class>, fields: HashMap<String, Value>) -> Self {
         Self {
             klass,
-            fields: Rc::new(RefCell::new(HashMap::new())),
+            fields: gc::alloc(fields),
+            data: Rc::new(RefCell::new(None)),
         }
     }
 
+    pub fn data(&self) -> Option<Rc<dyn Any>> {
+        self.data.borrow().clone()
+    }
+
+    pub fn set_data(&self, data: Rc<dyn Any>) {
+        *self.data.borrow_mut() = Some(data);
+    }
+
     pub fn get(&self, name: &Token) -> ResultExec<Value> {
         let key = name.to_string();
         self.fields
             .borrow()
             .get(&key)
             .cloned()
-            .or_else(|| {
-                let method = self.klass.find_method(&key)?;
-                let instance_ref = Rc::new(RefCell::new(self.clone()));
-                method.bind(instance_ref).map(Value::Callable)
-            })
+            .or_else(|| self.method(&key).map(Value::Callable))
             .ok_or_else(|| {
                 Error::undefined_var(format!("Undefined property '{}'.", key), Some(name.clone()))
             })
     }
 
-    pub fn set(&mut self, name: &Token, value: &Value) {
+    // a method of the class bound to this instance, ignoring fields
+    pub fn method(&self, name: &str) -> Option<Function> {
+        let method = self.klass.find_method(name)?;
+        method.bind(Value::Instance(gc::alloc(self.clone())))
+    }
+
+    pub fn set(&self, name: &Token, value: &Value) {
         self.fields
             .borrow_mut()
             .insert(name.to_string(), value.clone());
     }
+
+    // Accessors are returned bound to this instance; the interpreter calls them since running
+    // Lox code needs it.
+    pub fn getter(&self, name: &Token) -> Option<Function> {
+        let getter = self.klass.find_getter(&name.to_string())?;
+        getter.bind(Value::Instance(gc::alloc(self.clone())))
+    }
+
+    pub fn setter(&self, name: &Token) -> Option<Function> {
+        let setter = self.klass.find_setter(&name.to_string())?;
+        setter.bind(Value::Instance(gc::alloc(self.clone())))
+    }
+
+    pub fn is_instance_of(&self, klass: &Class) -> bool {
+        self.klass.is_subclass_of(klass)
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.borrow().get(name).cloned()
+    }
+
+    pub fn fields(&self) -> HashMap<String, Value> {
+        self.fields.borrow().clone()
+    }
+
+    pub fn has_getter(&self, name: &Token) -> bool {
+        self.klass.find_getter(&name.to_string()).is_some()
+    }
+}
+
+// Copies of an instance, like the `this` of its bound methods, share its fields: they are the
+// same instance.
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
+}
+
+impl Eq for Instance {}
+
+impl Hash for Instance {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.fields).hash(state);
+    }
+}
+
+impl Trace for Instance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.shared(&self.klass);
+        tracer.edge(&self.fields);
+    }
+}
+
+// Copies of an instance share its fields and data, only the fields, tracked on their own, are
+// emptied. The Rust data may still be used by a copy that is alive.
+impl Collect for Instance {
+    fn clear(&mut self) {}
 }
 
 impl Display for Instance {
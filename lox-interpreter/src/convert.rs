@@ -0,0 +1,325 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    class::{Class, Instance},
+    errors::{Error, ErrorKind},
+    function::Arity,
+    gc,
+    Interpreter, Value,
+};
+
+// A Rust type a Lox value can be read as, the arguments of natives registered with
+// `Interpreter::set_typed_fn`.
+pub trait FromLox: Sized {
+    // the type in error messages, written like the annotations of `lox check --types`
+    fn expected() -> String;
+
+    fn from_lox(value: &Value) -> Option<Self>;
+}
+
+// A Rust type that can be given to Lox, the return values of typed natives.
+pub trait IntoLox {
+    fn into_lox(self) -> Value;
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "Number",
+            Value::String(_) => "String",
+            Value::Bool(_) => "Bool",
+            Value::Null => "Nil",
+            Value::Callable(_) | Value::Constructor(_) => "Function",
+            Value::Class(_) => "Class",
+            Value::Trait(_) => "Trait",
+            Value::Instance(_) => "Instance",
+            Value::List(_) => "List",
+            Value::Module(_) => "Module",
+            Value::Enum(_) => "Enum",
+            Value::EnumValue(_) => "EnumValue",
+            Value::Generator(_) => "Generator",
+        }
+    }
+}
+
+impl FromLox for Value {
+    fn expected() -> String {
+        "Any".to_string()
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl IntoLox for Value {
+    fn into_lox(self) -> Value {
+        self
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> Value {
+        Value::Null
+    }
+}
+
+impl FromLox for f64 {
+    fn expected() -> String {
+        "Number".to_string()
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Value {
+        Value::Number(self as f32)
+    }
+}
+
+impl FromLox for f32 {
+    fn expected() -> String {
+        "Number".to_string()
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl IntoLox for f32 {
+    fn into_lox(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl FromLox for String {
+    fn expected() -> String {
+        "String".to_string()
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl FromLox for bool {
+    fn expected() -> String {
+        "Bool".to_string()
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+// nil is `None`
+impl<T: FromLox> FromLox for Option<T> {
+    fn expected() -> String {
+        format!("{}?", T::expected())
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_lox(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Value {
+        self.map_or(Value::Null, T::into_lox)
+    }
+}
+
+impl<T: FromLox> FromLox for Vec<T> {
+    fn expected() -> String {
+        format!("List of {}", T::expected())
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(items) => items.borrow().iter().map(T::from_lox).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+    fn into_lox(self) -> Value {
+        let items = self.into_iter().map(T::into_lox).collect();
+        Value::list(items)
+    }
+}
+
+// Lox has no maps: a map is an instance, its keys are the fields
+impl<T: FromLox> FromLox for HashMap<String, T> {
+    fn expected() -> String {
+        format!("Instance with {} fields", T::expected())
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Instance(instance) => instance
+                .borrow()
+                .fields()
+                .into_iter()
+                .map(|(name, value)| Some((name, T::from_lox(&value)?)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for HashMap<String, T> {
+    fn into_lox(self) -> Value {
+        let class = Class::new(
+            "Map".to_string(),
+            None,
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        let fields = self.into_iter().map(|(name, value)| (name, value.into_lox())).collect();
+        let instance = Instance::with_fields(Rc::new(class), fields);
+        Value::Instance(gc::alloc(instance))
+    }
+}
+
+// tuples are lists of the same length
+macro_rules! tuple_conversions {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: FromLox),+> FromLox for ($($name,)+) {
+            fn expected() -> String {
+                let types: Vec<String> = vec![$($name::expected()),+];
+                format!("({})", types.join(", "))
+            }
+
+            fn from_lox(value: &Value) -> Option<Self> {
+                let Value::List(items) = value else {
+                    return None;
+                };
+                let items = items.borrow();
+                if items.len() != <[usize]>::len(&[$($index),+]) {
+                    return None;
+                }
+                Some(($($name::from_lox(&items[$index])?,)+))
+            }
+        }
+
+        impl<$($name: IntoLox),+> IntoLox for ($($name,)+) {
+            fn into_lox(self) -> Value {
+                Value::list(vec![$(self.$index.into_lox()),+])
+            }
+        }
+    };
+}
+
+tuple_conversions!(A 0);
+tuple_conversions!(A 0, B 1);
+tuple_conversions!(A 0, B 1, C 2);
+tuple_conversions!(A 0, B 1, C 2, D 3);
+
+// What a typed native returns: a value, or a `Result` when it can fail
+pub trait NativeResult {
+    fn into_result(self) -> Result<Value, Error>;
+}
+
+impl<T: IntoLox> NativeResult for T {
+    fn into_result(self) -> Result<Value, Error> {
+        Ok(self.into_lox())
+    }
+}
+
+impl<T: IntoLox> NativeResult for Result<T, Error> {
+    fn into_result(self) -> Result<Value, Error> {
+        self.map(T::into_lox)
+    }
+}
+
+// A plain Rust function that can be registered as a native: its arity is the number of
+// parameters, and every argument is converted to the parameter type before the call.
+pub trait IntoNative<Args> {
+    fn arity(&self) -> Arity;
+
+    fn into_native(self, name: &str) -> impl Fn(&mut Interpreter, &[Value]) -> Result<Value, Error> + 'static;
+}
+
+fn argument<T: FromLox>(function: &str, arguments: &[Value], index: usize) -> Result<T, Error> {
+    let value = &arguments[index];
+    T::from_lox(value).ok_or_else(|| {
+        Error::new(
+            ErrorKind::WrongValueType(format!(
+                "Expected {} for argument {} of '{}', got {}.",
+                T::expected(),
+                index + 1,
+                function,
+                value.type_name()
+            )),
+            None,
+        )
+    })
+}
+
+macro_rules! into_native {
+    ($($name:ident $index:tt),*) => {
+        impl<F, R, $($name: FromLox),*> IntoNative<($($name,)*)> for F
+        where
+            F: Fn($($name),*) -> R + 'static,
+            R: NativeResult,
+        {
+            fn arity(&self) -> Arity {
+                Arity::exact(<[usize]>::len(&[$($index),*]))
+            }
+
+            #[allow(unused_variables)]
+            fn into_native(self, name: &str) -> impl Fn(&mut Interpreter, &[Value]) -> Result<Value, Error> + 'static {
+                let name = name.to_string();
+                move |_, arguments| self($(argument::<$name>(&name, arguments, $index)?),*).into_result()
+            }
+        }
+    };
+}
+
+into_native!();
+into_native!(A 0);
+into_native!(A 0, B 1);
+into_native!(A 0, B 1, C 2);
+into_native!(A 0, B 1, C 2, D 3);
+into_native!(A 0, B 1, C 2, D 3, E 4);
+into_native!(A 0, B 1, C 2, D 3, E 4, G 5);
@@ -0,0 +1,491 @@
+use std::{
+    any::Any,
+    fmt::Display,
+    fs,
+    io::{BufRead, Write},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use lox_syntax::{parse_program, Error as SyntaxError, Lexer};
+
+use crate::{
+    convert::IntoNative,
+    errors::{Error, ErrorKind},
+    foreign::ForeignClass,
+    function::Arity,
+    limits::Limits,
+    output::Capture,
+    Interpreter, Resolver, Value,
+};
+
+// Runs Lox code for a host application: it lexes, parses, resolves and interprets a source in
+// one call, and keeps the globals between calls.
+//
+// let mut engine = Engine::builder().module(lox_std::set_stdlib).build();
+// engine.eval("fun twice(x) { return x * 2; }")?;
+// let four = engine.call_function("twice", &[Value::Number(2.0)])?;
+pub struct Engine {
+    interpreter: Interpreter,
+}
+
+// Why a source didn't run, by the step that failed
+#[derive(Debug)]
+pub enum Diagnostics {
+    Io(std::io::Error),
+    Syntax(SyntaxError),
+    // resolver errors, reported before anything runs
+    Static(Error),
+    Runtime(Error),
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    // Runs `source` and gives back the value of its last statement when it is an expression,
+    // nil otherwise
+    pub fn eval(&mut self, source: &str) -> Result<Value, Diagnostics> {
+        let tokens = Lexer::new(source).scan_tokens().map_err(Diagnostics::Syntax)?;
+        let statements = parse_program(&tokens).map_err(Diagnostics::Syntax)?;
+
+        Resolver::for_module(self.interpreter.module())
+            .resolve_stmts(&statements)
+            .map_err(Diagnostics::Static)?;
+        self.interpreter
+            .interpret_value(&statements)
+            .map_err(Diagnostics::Runtime)
+    }
+
+    // Runs a script file, its imports are resolved relative to its directory
+    pub fn run_file(&mut self, path: &str) -> Result<Value, Diagnostics> {
+        let source = fs::read_to_string(path).map_err(Diagnostics::Io)?;
+        self.interpreter.set_script_path(path).map_err(Diagnostics::Io)?;
+        self.eval(&source)
+    }
+
+    // A top-level variable of the scripts run so far, or a native
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.module().globals().borrow().get(name).ok()
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.module().globals().borrow_mut().define(name, value);
+    }
+
+    pub fn call_function(&mut self, name: &str, arguments: &[Value]) -> Result<Value, Diagnostics> {
+        let Some(function) = self.get_global(name) else {
+            return Err(Diagnostics::Runtime(Error::new(
+                ErrorKind::UndefinedVar(format!("Undefined variable '{}'.", name)),
+                None,
+            )));
+        };
+
+        self.interpreter
+            .call_value(&function, arguments)
+            .map_err(Diagnostics::Runtime)
+    }
+
+    // Setting the flag, from any thread, stops the running script
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.interpreter.cancel_flag()
+    }
+
+    // Sends what scripts print to a new `Capture` from now on, and returns it
+    pub fn capture(&mut self) -> Capture {
+        let capture = Capture::new();
+        self.interpreter.set_output(Box::new(capture.clone()));
+        capture
+    }
+
+    // writes the diagnostics to the error output of the interpreter
+    pub fn report(&mut self, diagnostics: &Diagnostics) {
+        // nowhere left to report a failing error output
+        let _ = writeln!(self.interpreter.error_output(), "{}", diagnostics);
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sets up the natives of an `Engine` before any code runs
+pub struct EngineBuilder {
+    interpreter: Interpreter,
+}
+
+impl EngineBuilder {
+    pub fn native(
+        mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, Error> + 'static,
+    ) -> Self {
+        self.interpreter.set_global_fn(name, arity, func);
+        self
+    }
+
+    // a plain Rust function, its arguments are converted to the parameter types
+    pub fn function<Args>(mut self, name: &str, func: impl IntoNative<Args>) -> Self {
+        self.interpreter.set_typed_fn(name, func);
+        self
+    }
+
+    pub fn class<T: Any>(mut self, class: ForeignClass<T>) -> Self {
+        self.interpreter.set_global_class(class);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.interpreter.set_limits(limits);
+        self
+    }
+
+    // where `print` writes, stdout by default
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.interpreter.set_output(Box::new(output));
+        self
+    }
+
+    // where `Engine::report` writes, stderr by default
+    pub fn error_output(mut self, error_output: impl Write + 'static) -> Self {
+        self.interpreter.set_error_output(Box::new(error_output));
+        self
+    }
+
+    // what natives read with `Interpreter::read_line`, stdin by default
+    pub fn input(mut self, input: impl BufRead + 'static) -> Self {
+        self.interpreter.set_input(Box::new(input));
+        self
+    }
+
+    // `register` defines a group of natives, like the standard library
+    pub fn module(mut self, register: impl FnOnce(&mut Interpreter)) -> Self {
+        register(&mut self.interpreter);
+        self
+    }
+
+    pub fn build(self) -> Engine {
+        Engine {
+            interpreter: self.interpreter,
+        }
+    }
+}
+
+impl Diagnostics {
+    // the exit code of the `lox` command for this failure
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Diagnostics::Io(_) => 66,
+            Diagnostics::Syntax(_) | Diagnostics::Static(_) => 65,
+            Diagnostics::Runtime(_) => 70,
+        }
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostics::Io(e) => write!(f, "{}", e),
+            Diagnostics::Syntax(e) => write!(f, "{}", e),
+            Diagnostics::Static(e) | Diagnostics::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        collections::{HashMap, HashSet},
+        rc::Rc,
+        sync::atomic::Ordering,
+        thread,
+        time::Duration,
+    };
+
+    use super::{Diagnostics, Engine};
+    use crate::{
+        convert::{FromLox, IntoLox},
+        errors::ErrorKind,
+        foreign::ForeignClass,
+        function::{Arity, Function},
+        limits::Limits,
+        output::Capture,
+        Value,
+    };
+
+    #[test]
+    fn test_eval_keeps_globals() {
+        let mut engine = Engine::new();
+        engine.eval("var x = 20; fun twice(n) { return n * 2; }").ok();
+
+        assert!(matches!(engine.eval("x + 2;"), Ok(Value::Number(n)) if n == 22.0));
+        assert!(matches!(engine.call_function("twice", &[Value::Number(4.0)]), Ok(Value::Number(n)) if n == 8.0));
+
+        engine.set_global("x", Value::String("set".to_string()));
+        assert!(matches!(engine.get_global("x"), Some(Value::String(s)) if s == "set"));
+        assert!(engine.get_global("y").is_none());
+    }
+
+    #[test]
+    fn test_builder_natives() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let mut engine = Engine::builder()
+            .native("count", Arity::at_least(0), move |_, args| {
+                counter.set(counter.get() + args.len());
+                Ok(Value::Null)
+            })
+            .build();
+
+        engine.eval("count(1, 2); count(3);").ok();
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_typed_functions() {
+        fn add(a: f64, b: f64) -> f64 {
+            a + b
+        }
+
+        let mut engine = Engine::builder()
+            .function("add", add)
+            .function("greet", |name: Option<String>| format!("hi {}", name.unwrap_or_default()))
+            .function("swap", |pair: (f64, String)| (pair.1, pair.0))
+            .function("total", |values: HashMap<String, Vec<f64>>| -> f64 {
+                values.values().flatten().sum()
+            })
+            .build();
+
+        assert!(matches!(engine.eval("add(1, 2);"), Ok(Value::Number(n)) if n == 3.0));
+        assert!(matches!(engine.eval("greet(nil);"), Ok(Value::String(s)) if s == "hi "));
+        assert!(matches!(engine.eval("swap([1, \"a\"]);"), Ok(Value::List(l)) if l.borrow().len() == 2));
+
+        let script = "class Bag {} var bag = Bag(); bag.a = [1, 2]; bag.b = [3]; total(bag);";
+        assert!(matches!(engine.eval(script), Ok(Value::Number(n)) if n == 6.0));
+
+        let Err(Diagnostics::Runtime(error)) = engine.eval("add(1, \"2\");") else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(
+            error.kind().to_string(),
+            "Wrong value type: Expected Number for argument 2 of 'add', got String."
+        );
+        assert!(matches!(engine.eval("add(1);"), Err(Diagnostics::Runtime(_))));
+    }
+
+    #[test]
+    fn test_foreign_classes() {
+        struct User {
+            name: String,
+            logins: f64,
+        }
+
+        let user = ForeignClass::<User>::new("User")
+            .constructor(Arity::exact(1), |_, args| {
+                Ok(User {
+                    name: args[0].to_string(),
+                    logins: 0.0,
+                })
+            })
+            .getter("name", |user| user.name.clone().into_lox())
+            .setter("name", |user, value| {
+                user.name = String::from_lox(&value).unwrap_or_default();
+                Ok(())
+            })
+            .getter("logins", |user| user.logins.into_lox())
+            .method("login", Arity::exact(0), |_, user, _| {
+                user.logins += 1.0;
+                Ok(user.logins.into_lox())
+            });
+        let mut engine = Engine::builder().class(user).build();
+
+        let script = "
+            class Admin < User {
+                init(name) { super.init(name + \"!\"); }
+                login() { return super.login() * 10; }
+            }
+            var admin = Admin(\"root\");
+            admin.name = admin.name + \"?\";
+            admin.login();
+        ";
+        assert!(matches!(engine.eval(script), Ok(Value::Number(n)) if n == 10.0));
+        assert!(matches!(engine.eval("admin.name;"), Ok(Value::String(s)) if s == "root!?"));
+        assert!(matches!(engine.eval("admin.logins;"), Ok(Value::Number(n)) if n == 1.0));
+        assert!(matches!(engine.eval("admin.logins = 5;"), Err(Diagnostics::Runtime(_))));
+
+        // an object of the host, shared with the script
+        let shared = Rc::new(RefCell::new(User {
+            name: "guest".to_string(),
+            logins: 0.0,
+        }));
+        let class = engine.get_global("User").unwrap();
+        let guest = ForeignClass::instance(&class, Rc::clone(&shared)).unwrap();
+        engine.set_global("guest", guest);
+        engine.eval("guest.login(); guest.login();").ok();
+        assert_eq!(shared.borrow().logins, 2.0);
+        assert!(engine.get_global("admin").unwrap().foreign::<User>().is_some());
+    }
+
+    #[test]
+    fn test_output_and_input() {
+        let errors = Capture::new();
+        let mut engine = Engine::builder()
+            .error_output(errors.clone())
+            .input("first\nsecond\n".as_bytes())
+            .native("input", Arity::exact(0), |interpreter, _| {
+                Ok(interpreter.read_line().unwrap().into_lox())
+            })
+            .build();
+        let output = engine.capture();
+
+        engine.eval("print input(); print 1 + 2; print input(); print input();").ok();
+        assert_eq!(output.contents(), "first\n3\nsecond\nnull\n");
+
+        let diagnostics = engine.eval("print 1 - nil;").unwrap_err();
+        engine.report(&diagnostics);
+        assert!(errors.contents().contains("Both operands must be a number."));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            steps: Some(10_000),
+            ..Limits::default()
+        };
+        let mut engine = Engine::builder().limits(limits).build();
+        let Err(Diagnostics::Runtime(error)) = engine.eval("while (true) {}") else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(error.kind().to_string(), "Interrupted: Step limit of 10000 exceeded.");
+        // every run has its own budget
+        assert!(engine.eval("var i = 0; while (i < 100) i = i + 1;").is_ok());
+
+        let limits = Limits {
+            time: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        };
+        let mut engine = Engine::builder().limits(limits).build();
+        let Err(Diagnostics::Runtime(error)) = engine.eval("while (true) {}") else {
+            panic!("expected a runtime error");
+        };
+        assert!(matches!(error.kind(), ErrorKind::Interrupted(_)));
+
+        let mut engine = Engine::new();
+        let flag = engine.cancel_flag();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            flag.store(true, Ordering::Relaxed);
+        });
+        let Err(Diagnostics::Runtime(error)) = engine.eval("while (true) {}") else {
+            panic!("expected a runtime error");
+        };
+        canceller.join().unwrap();
+        assert_eq!(error.kind().to_string(), "Interrupted: Cancelled by the host.");
+
+        // the flag stays set until it is cleared
+        assert!(engine.eval("1;").is_err());
+        engine.cancel_flag().store(false, Ordering::Relaxed);
+        assert!(engine.eval("1;").is_ok());
+
+        // test threads have a small stack
+        let limits = Limits {
+            depth: Some(50),
+            ..Limits::default()
+        };
+        let mut engine = Engine::builder().limits(limits).build();
+        let Err(Diagnostics::Runtime(error)) = engine.eval("fun f() { f(); } f();") else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(error.kind().to_string(), "Stack overflow: Maximum call depth of 50 exceeded.");
+        assert!(engine.eval("fun g(n) { if (n > 0) g(n - 1); } g(40);").is_ok());
+
+        // resuming a generator runs its body further down the stack
+        let script = "fun gen(n) { for (var x in gen(n + 1)) { yield x; } } for (var y in gen(0)) print y;";
+        let Err(Diagnostics::Runtime(error)) = engine.eval(script) else {
+            panic!("expected a runtime error");
+        };
+        assert!(matches!(error.kind(), ErrorKind::StackOverflow(_)));
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let mut engine = Engine::new();
+        engine
+            .eval("class Node {} fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }")
+            .ok();
+
+        // a node in its own field, and a closure stored in the scope it captures
+        let node = match engine.eval("var node = Node(); node.me = node; node;") {
+            Ok(Value::Instance(node)) => Rc::downgrade(&node),
+            _ => panic!("expected an instance"),
+        };
+        let closure = match engine.eval("var inc = counter(); inc;") {
+            Ok(Value::Callable(Function::Custom { closure, .. })) => Rc::downgrade(&closure),
+            _ => panic!("expected a function"),
+        };
+
+        // both are still reachable from the globals
+        engine.interpreter().collect_garbage();
+        assert!(matches!(engine.eval("node.me.me;"), Ok(Value::Instance(_))));
+        assert!(matches!(engine.eval("inc();"), Ok(Value::Number(n)) if n == 1.0));
+
+        engine.eval("node = nil; inc = nil;").ok();
+        let stats = engine.interpreter().gc_stats();
+        assert!(engine.interpreter().collect_garbage() >= 3);
+        assert!(node.upgrade().is_none());
+        assert!(closure.upgrade().is_none());
+        assert_eq!(engine.interpreter().gc_stats().collections, stats.collections + 1);
+        assert!(stats.bytes_allocated > 0);
+
+        // a value the host holds is kept, with everything it reaches
+        let Ok(Value::Instance(held)) = engine.eval("var held = Node(); held.me = held; held;") else {
+            panic!("expected an instance");
+        };
+        engine.eval("held = nil;").ok();
+        engine.interpreter().collect_garbage();
+        assert!(held.borrow().field("me").is_some());
+    }
+
+    // the hash of a value only reads identities, never what is behind its `RefCell`s
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn test_values_as_keys() {
+        let mut engine = Engine::new();
+        let Ok(Value::List(list)) = engine.eval("class A {} var a = A(); [a, a, A(), 0, \"0\", nil, 0 / 0, 0 / 0];") else {
+            panic!("expected a list");
+        };
+
+        let keys: HashSet<Value> = list.borrow().iter().cloned().collect();
+        assert_eq!(keys.len(), 6);
+        assert!(keys.contains(&Value::Number(f32::NAN)));
+        assert!(keys.contains(&engine.get_global("a").unwrap()));
+        assert!(keys.contains(&Value::Number(-0.0)));
+        assert!(!keys.contains(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut engine = Engine::new();
+
+        assert!(matches!(engine.eval("print ;"), Err(Diagnostics::Syntax(_))));
+        assert!(matches!(engine.eval("{ var a = 1; }"), Err(Diagnostics::Static(_))));
+        assert!(matches!(engine.eval("1 - \"a\";"), Err(Diagnostics::Runtime(_))));
+        assert!(matches!(engine.call_function("missing", &[]), Err(Diagnostics::Runtime(_))));
+    }
+}
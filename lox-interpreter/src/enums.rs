@@ -0,0 +1,197 @@
+use std::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use lox_syntax::Token;
+
+use crate::{
+    errors::{Error, ResultExec},
+    function::Arity,
+    gc::{Trace, Tracer},
+    interpreter::LoxCallable,
+    Value,
+};
+
+#[derive(Clone, Debug)]
+pub struct Variant {
+    name: String,
+    fields: Vec<String>,
+}
+
+impl Variant {
+    pub fn new(name: String, fields: Vec<String>) -> Self {
+        Self { name, fields }
+    }
+}
+
+// An `enum` declaration. Its values remember the enum they belong to, so variants with the same
+// name in two enums are never equal.
+#[derive(Clone, Debug)]
+pub struct Enum {
+    name: String,
+    variants: Vec<Variant>,
+}
+
+impl Enum {
+    pub fn new(name: String, variants: Vec<Variant>) -> Self {
+        Self { name, variants }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // `Shape.Circle` is the constructor of a variant with fields, `Shape.Empty` is the value itself
+    pub fn get(enum_: &Rc<Enum>, name: &Token) -> ResultExec<Value> {
+        let variant = enum_
+            .variants
+            .iter()
+            .position(|v| v.name == name.to_string())
+            .ok_or_else(|| {
+                Error::undefined_var(
+                    format!("Enum '{}' has no variant '{}'.", enum_.name, name),
+                    Some(name.clone()),
+                )
+            })?;
+
+        let constructor = Constructor {
+            enum_: Rc::clone(enum_),
+            variant,
+        };
+        if enum_.variants[variant].fields.is_empty() {
+            return Ok(Value::EnumValue(Rc::new(constructor.build(Vec::new()))));
+        }
+        Ok(Value::Constructor(constructor))
+    }
+}
+
+impl Display for Enum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+// Builds the values of one variant, called like a function with one argument per field
+#[derive(Clone, Debug)]
+pub struct Constructor {
+    enum_: Rc<Enum>,
+    variant: usize,
+}
+
+impl Constructor {
+    fn build(&self, values: Vec<Value>) -> EnumValue {
+        EnumValue {
+            enum_: Rc::clone(&self.enum_),
+            variant: self.variant,
+            values,
+        }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.enum_.variants[self.variant].fields
+    }
+
+    // whether `value` was built by this constructor
+    pub fn built(&self, value: &EnumValue) -> bool {
+        Rc::ptr_eq(&self.enum_, &value.enum_) && self.variant == value.variant
+    }
+}
+
+impl LoxCallable for Constructor {
+    fn call(&self, _interpreter: &mut crate::Interpreter, arguments: &Vec<Value>) -> ResultExec<Value> {
+        Ok(Value::EnumValue(Rc::new(self.build(arguments.clone()))))
+    }
+
+    fn arity(&self) -> Arity {
+        Arity::exact(self.fields().len())
+    }
+}
+
+impl PartialEq for Constructor {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.enum_, &other.enum_) && self.variant == other.variant
+    }
+}
+
+impl Eq for Constructor {}
+
+impl Hash for Constructor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.enum_).hash(state);
+        self.variant.hash(state);
+    }
+}
+
+impl Display for Constructor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.enum_.name, self.enum_.variants[self.variant].name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EnumValue {
+    enum_: Rc<Enum>,
+    variant: usize,
+    values: Vec<Value>,
+}
+
+impl EnumValue {
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    // same enum and variant, the caller compares the values
+    pub fn same_variant(&self, other: &EnumValue) -> bool {
+        Rc::ptr_eq(&self.enum_, &other.enum_) && self.variant == other.variant
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.enum_.variants[self.variant]
+            .fields
+            .iter()
+            .position(|field| field == name)
+            .map(|i| self.values[i].clone())
+    }
+}
+
+impl PartialEq for EnumValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.same_variant(other) && self.values == other.values
+    }
+}
+
+impl Eq for EnumValue {}
+
+impl Hash for EnumValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.enum_).hash(state);
+        self.variant.hash(state);
+        self.values.hash(state);
+    }
+}
+
+impl Trace for EnumValue {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.values.trace(tracer);
+    }
+}
+
+impl Display for EnumValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.enum_.name, self.enum_.variants[self.variant].name)?;
+        if self.values.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, "(")?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        write!(f, ")")
+    }
+}
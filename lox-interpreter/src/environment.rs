@@ -1,5 +1,6 @@
 use crate::{
     errors::{Error, ResultExec},
+    gc::{Collect, Trace, Tracer},
     value::Value,
 };
 use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
@@ -31,7 +32,7 @@ impl Environment {
 
     pub fn get(&self, name: &str) -> ResultExec<Value> {
         match self.values.get(name) {
-            Some(v) => return Ok(v.clone()),
+            Some(v) => Ok(v.clone()),
             None => {
                 if let Some(env) = &self.enclosing {
                     return env.borrow().get(name);
@@ -69,7 +70,7 @@ impl Environment {
     pub fn assign(&mut self, name: &str, value: Value) -> ResultExec<()> {
         if self.values.contains_key(name) {
             self.values.insert(name.to_string(), value);
-            return Ok(());
+            Ok(())
         } else {
             if let Some(enclosing) = &self.enclosing {
                 enclosing.borrow_mut().assign(name, value)
@@ -82,6 +83,21 @@ impl Environment {
         }
     }
 
+    // Whether both scopes only hold the same `this` or `super`, over the same scope: they bind
+    // the same method to the same receiver.
+    pub fn same_binding(a: &Rc<RefCell<Environment>>, b: &Rc<RefCell<Environment>>) -> bool {
+        let (a, b) = (a.borrow(), b.borrow());
+        let binding = |env: &Environment| {
+            let (name, value) = env.values.iter().next()?;
+            let bound = env.values.len() == 1 && (name == "this" || name == "super");
+            bound.then(|| (name.clone(), value.clone()))
+        };
+        match (&a.enclosing, &b.enclosing, binding(&a), binding(&b)) {
+            (Some(c), Some(d), Some(x), Some(y)) => Rc::ptr_eq(c, d) && x == y,
+            _ => false,
+        }
+    }
+
     pub fn assign_at(env_ref: Rc<RefCell<Environment>>, distance: usize, name: &str, value: Value) {
         Self::ancestor(env_ref, distance)
             .borrow_mut()
@@ -90,6 +106,24 @@ impl Environment {
     }
 }
 
+impl Trace for Environment {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.values.values() {
+            value.trace(tracer);
+        }
+        if let Some(enclosing) = &self.enclosing {
+            tracer.edge(enclosing);
+        }
+    }
+}
+
+impl Collect for Environment {
+    fn clear(&mut self) {
+        self.values.clear();
+        self.enclosing = None;
+    }
+}
+
 impl Display for Environment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Environment {{")?;
@@ -30,9 +30,18 @@ pub enum ErrorKind {
     UnexpectedOpt(String),
     WrongValueType(String),
     NotCallable(String),
+    WrongArity(String),
     UnusedVariable(String),
     InvalidContext(String),
     UndefinedVar(String),
+    ImportError(String),
+    NoMatch(String),
+    InvalidAssignment(String),
+    TypeError(String),
+    IoError(String),
+    StackOverflow(String),
+    Interrupted(String),
+    PermissionDenied(String),
 }
 
 macro_rules! error_constructors {
@@ -60,9 +69,35 @@ error_constructors! {
     (unexpected_opt, UnexpectedOpt, desc),
     (wrong_value_type, WrongValueType, msg),
     (not_callable, NotCallable, name),
+    (wrong_arity, WrongArity, msg),
     (unused_variable, UnusedVariable, name),
     (invalid_context, InvalidContext, msg),
     (undefined_var, UndefinedVar, desc),
+    (import_error, ImportError, msg),
+    (no_match, NoMatch, msg),
+    (invalid_assignment, InvalidAssignment, msg),
+    (io_error, IoError, msg),
+    (stack_overflow, StackOverflow, msg),
+    (interrupted, Interrupted, msg),
+    (permission_denied, PermissionDenied, msg),
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn location(&self) -> Option<&Token> {
+        self.location.as_ref()
+    }
+
+    // keeps the location the error has, else takes `location`
+    pub(crate) fn located(mut self, location: Option<&Token>) -> Self {
+        if self.location.is_none() {
+            self.location = location.cloned();
+        }
+        self
+    }
 }
 
 impl ControlFlow {
@@ -78,7 +113,7 @@ impl ControlFlow {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.location {
-            Some(token) => write!(f, "[line {}] Error at '{}': {}", token.line, token.to_string(), self.kind),
+            Some(token) => write!(f, "[line {}] Error at '{}': {}", token.line, token, self.kind),
             None => write!(f, "Error: {}", self.kind),
         }
     }
@@ -95,9 +130,18 @@ impl fmt::Display for ErrorKind {
             Self::UnexpectedOpt(desc) => write!(f, "Unexpected optional construct: {}", desc),
             Self::WrongValueType(msg) => write!(f, "Wrong value type: {}", msg),
             Self::NotCallable(name) => write!(f, "'{}' is not callable", name),
+            Self::WrongArity(msg) => write!(f, "Wrong arity: {}", msg),
             Self::UnusedVariable(name) => write!(f, "Variable '{}' is declared but never used", name),
             Self::InvalidContext(msg) => write!(f, "Invalid context: {}", msg),
             Self::UndefinedVar(desc) => write!(f, "Undefined variable: {}", desc),
+            Self::ImportError(msg) => write!(f, "Import error: {}", msg),
+            Self::NoMatch(msg) => write!(f, "No match: {}", msg),
+            Self::InvalidAssignment(msg) => write!(f, "Invalid assignment: {}", msg),
+            Self::TypeError(msg) => write!(f, "Type error: {}", msg),
+            Self::IoError(msg) => write!(f, "I/O error: {}", msg),
+            Self::StackOverflow(msg) => write!(f, "Stack overflow: {}", msg),
+            Self::Interrupted(msg) => write!(f, "Interrupted: {}", msg),
+            Self::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
         }
     }
 }
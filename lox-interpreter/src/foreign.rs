@@ -0,0 +1,186 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc};
+
+use crate::{
+    class::{Class, Instance},
+    errors::{Error, ErrorKind},
+    function::{Arity, Function},
+    gc,
+    Interpreter, Value,
+};
+
+type Constructor<T> = Rc<dyn Fn(&mut Interpreter, &[Value]) -> Result<T, Error>>;
+
+// A Rust type registered as a Lox class. Its instances are ordinary instances holding a `T`, so
+// Lox code calls their methods, reads and writes their properties, prints them and subclasses
+// the class like any other; the methods and properties registered here work on the `T`.
+//
+// let user = ForeignClass::<User>::new("User")
+//     .constructor(Arity::exact(1), |_, args| Ok(User::new(String::from_lox(&args[0]).unwrap())))
+//     .getter("name", |user| user.name.clone().into_lox())
+//     .method("rename", Arity::exact(1), |_, user, args| { ... });
+pub struct ForeignClass<T> {
+    name: String,
+    constructor: Option<(Arity, Constructor<T>)>,
+    methods: HashMap<String, Function>,
+    getters: HashMap<String, Function>,
+    setters: HashMap<String, Function>,
+    static_methods: HashMap<String, Function>,
+    data: PhantomData<T>,
+}
+
+impl<T: Any> ForeignClass<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            constructor: None,
+            methods: HashMap::new(),
+            getters: HashMap::new(),
+            setters: HashMap::new(),
+            static_methods: HashMap::new(),
+            data: PhantomData,
+        }
+    }
+
+    // Builds the `T` of a new instance from the arguments of the call to the class. Without a
+    // constructor, instances can only be created from Rust with `ForeignClass::instance`.
+    pub fn constructor(
+        mut self,
+        arity: Arity,
+        func: impl Fn(&mut Interpreter, &[Value]) -> Result<T, Error> + 'static,
+    ) -> Self {
+        self.constructor = Some((arity, Rc::new(func)));
+        self
+    }
+
+    pub fn method(
+        mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&mut Interpreter, &mut T, &[Value]) -> Result<Value, Error> + 'static,
+    ) -> Self {
+        let class = self.name.clone();
+        let method = Self::native(arity, move |interpreter, this, arguments| {
+            let data = Self::data_of(&class, this)?;
+            let mut data = data.try_borrow_mut().map_err(|_| in_use(&class))?;
+            func(interpreter, &mut data, arguments)
+        });
+        self.methods.insert(name.to_string(), method);
+        self
+    }
+
+    // `obj.name`
+    pub fn getter(mut self, name: &str, func: impl Fn(&T) -> Value + 'static) -> Self {
+        let class = self.name.clone();
+        let getter = Self::native(Arity::exact(0), move |_, this, _| {
+            let data = Self::data_of(&class, this)?;
+            let data = data.try_borrow().map_err(|_| in_use(&class))?;
+            Ok(func(&data))
+        });
+        self.getters.insert(name.to_string(), getter);
+        self
+    }
+
+    // `obj.name = value`, a property with a getter and no setter is read-only
+    pub fn setter(mut self, name: &str, func: impl Fn(&mut T, Value) -> Result<(), Error> + 'static) -> Self {
+        let class = self.name.clone();
+        let setter = Self::native(Arity::exact(1), move |_, this, arguments| {
+            let data = Self::data_of(&class, this)?;
+            let mut data = data.try_borrow_mut().map_err(|_| in_use(&class))?;
+            func(&mut data, arguments[0].clone())?;
+            Ok(Value::Null)
+        });
+        self.setters.insert(name.to_string(), setter);
+        self
+    }
+
+    // a method called on the class, `User.find(1)`
+    pub fn static_method(
+        mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, Error> + 'static,
+    ) -> Self {
+        let method = Self::native(arity, move |interpreter, _, arguments| func(interpreter, arguments));
+        self.static_methods.insert(name.to_string(), method);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn build(mut self) -> Value {
+        if let Some((arity, constructor)) = self.constructor.take() {
+            // `init` stores the `T` in `this`, it runs for subclasses through `super.init()` too
+            let init = Self::native(arity, move |interpreter, this, arguments| {
+                let data = constructor(interpreter, arguments)?;
+                if let Value::Instance(instance) = this {
+                    instance.borrow().set_data(Rc::new(RefCell::new(data)));
+                }
+                Ok(Value::Null)
+            });
+            self.methods.insert("init".to_string(), init);
+        }
+
+        Value::Class(Rc::new(Class::new(
+            self.name,
+            None,
+            self.methods,
+            self.getters,
+            self.setters,
+            self.static_methods,
+        )))
+    }
+
+    // An instance of `class` around an existing Rust value, shared with the caller rather than
+    // copied. `None` when `class` isn't a class.
+    pub fn instance(class: &Value, data: Rc<RefCell<T>>) -> Option<Value> {
+        let Value::Class(class) = class else {
+            return None;
+        };
+
+        let instance = Instance::new(Rc::clone(class));
+        instance.set_data(data);
+        Some(Value::Instance(gc::alloc(instance)))
+    }
+
+    fn native(
+        arity: Arity,
+        body: impl Fn(&mut Interpreter, &Value, &[Value]) -> Result<Value, Error> + 'static,
+    ) -> Function {
+        Function::NativeMethod {
+            arity,
+            body: Rc::new(body),
+            this: None,
+        }
+    }
+
+    fn data_of(class: &str, this: &Value) -> Result<Rc<RefCell<T>>, Error> {
+        this.foreign::<T>().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidContext(format!(
+                    "This instance has no {} data, the initializer of a subclass must call super.init().",
+                    class
+                )),
+                None,
+            )
+        })
+    }
+}
+
+fn in_use(class: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidContext(format!("The {} data is already in use.", class)),
+        None,
+    )
+}
+
+impl Value {
+    // the Rust value behind an instance of a `ForeignClass<T>` or one of its subclasses
+    pub fn foreign<T: Any>(&self) -> Option<Rc<RefCell<T>>> {
+        let Value::Instance(instance) = self else {
+            return None;
+        };
+        instance.borrow().data()?.downcast::<RefCell<T>>().ok()
+    }
+}
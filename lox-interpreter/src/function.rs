@@ -1,32 +1,77 @@
-use std::{cell::RefCell, fmt::Debug, rc::Rc};
+use std::{cell::RefCell, fmt::{Debug, Display}, hash::{Hash, Hasher}, rc::Rc};
 
-use lox_syntax::{Stmt, Token};
+use lox_syntax::{Param, Stmt, Token};
 
-use crate::{class::Instance, environment::Environment, errors::{ControlFlow, ResultExec, RuntimeControl}, interpreter::LoxCallable, value::Value, Interpreter};
+use crate::{environment::Environment, gc::{self, Trace, Tracer}, generator::Generator, module::Module, errors::{ControlFlow, Error, ResultExec, RuntimeControl}, interpreter::LoxCallable, value::Value, Interpreter};
+
+// The body of a function written in Rust. It can capture state, call back into the interpreter,
+// and fail: an error without a location is reported at the call site.
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, Error>;
+
+// The body of a method written in Rust, called with the instance it is bound to
+pub type NativeMethodFn = dyn Fn(&mut Interpreter, &Value, &[Value]) -> Result<Value, Error>;
 
 #[derive(Clone)]
 pub enum Function {
     Native {
-        arity: usize,
-        body: Box<fn(&Vec<Value>) -> Value>,
+        arity: Arity,
+        body: Rc<NativeFn>,
     },
     Custom {
-        params: Rc<Vec<Token>>,
+        name: Rc<str>,
+        params: Rc<Vec<Param>>,
         body: Rc<Vec<Stmt>>,
         closure: Rc<RefCell<Environment>>,
+        module: Rc<Module>,
         is_initializer: bool,
     },
+    // a method of a class registered from Rust, `this` is set once it is bound
+    NativeMethod {
+        arity: Arity,
+        body: Rc<NativeMethodFn>,
+        this: Option<Box<Value>>,
+    },
+    // `generator.next`, resumes the generator it was read from
+    GeneratorNext {
+        generator: Rc<RefCell<Generator>>,
+    },
 }
 
 impl Function {
-    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> Option<Function>{
-        if let Self::Custom { params, body, closure , is_initializer} = self {
+    // `this` is an instance for methods and the class itself for static methods
+    pub fn bind(&self, this: Value) -> Option<Function>{
+        if let Self::NativeMethod { arity, body, .. } = self {
+            return Some(Function::NativeMethod {
+                arity: *arity,
+                body: Rc::clone(body),
+                this: Some(Box::new(this)),
+            });
+        }
+        self.with_binding("this", this)
+    }
+
+    // trait methods are re-closed over the superclass of the class they are composed into
+    pub fn with_super(&self, superclass: Value) -> Option<Function> {
+        self.with_binding("super", superclass)
+    }
+
+    pub fn param_names(&self) -> Vec<String> {
+        match self {
+            Self::Custom { params, .. } => params.iter().map(|p| p.name.to_string()).collect(),
+            Self::Native { .. } | Self::NativeMethod { .. } | Self::GeneratorNext { .. } => Vec::new(),
+        }
+    }
+
+    fn with_binding(&self, name: &str, value: Value) -> Option<Function> {
+        if let Self::Custom { name: function, params, body, closure, module, is_initializer } = self {
             let mut environment = Environment::from(closure);
-            environment.define("this", Value::Instance(instance));
+            environment.define(name, value);
             return Some(Function::Custom { 
+                name: Rc::clone(function),
                 params: Rc::clone(params), 
                 body: Rc::clone(body), 
-                closure: Rc::new(RefCell::new(environment)),
+                closure: gc::alloc(environment),
+                module: Rc::clone(module),
                 is_initializer: *is_initializer 
             });
         }
@@ -36,20 +81,133 @@ impl Function {
 }
 
 impl LoxCallable for Function {
-     fn call(&self, interpreter: &mut Interpreter, arguments: &Vec<Value>) -> ResultExec<Value> {
+    fn call(&self, interpreter: &mut Interpreter, arguments: &Vec<Value>) -> ResultExec<Value> {
+        self.invoke(interpreter, None, arguments, &[])
+    }
+
+    fn call_named(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &Vec<Value>,
+        named: &[(Token, Value)],
+    ) -> ResultExec<Value> {
+        self.invoke(interpreter, Some(paren), arguments, named)
+    }
+
+    fn arity(&self) -> Arity {
+        match self {
+            Function::Native { arity, .. } | Function::NativeMethod { arity, .. } => *arity,
+            Function::GeneratorNext { .. } => Arity::exact(0),
+            Function::Custom { params, .. } => Arity {
+                min: params.iter().filter(|p| p.default.is_none() && !p.rest).count(),
+                max: match params.last() {
+                    Some(p) if p.rest => None,
+                    _ => Some(params.len()),
+                },
+            },
+        }
+    }
+}
+
+impl Function {
+    // `paren` is the call site, when there is one, for errors binding the arguments
+    pub(crate) fn invoke(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: Option<&Token>,
+        arguments: &[Value],
+        named: &[(Token, Value)],
+    ) -> ResultExec<Value> {
+        // natives count too: calling back into Lox, or resuming a generator, goes deeper
+        interpreter.enter_call(paren)?;
+        let result = self.run(interpreter, paren, arguments, named);
+        interpreter.exit_call();
+        result
+    }
+
+    fn run(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: Option<&Token>,
+        arguments: &[Value],
+        named: &[(Token, Value)],
+    ) -> ResultExec<Value> {
         match self {
-            Function::Native { body, .. } => Ok(body(arguments)),
-            Function::Custom { params, body , closure, is_initializer } => {
-                let environment = Rc::new(RefCell::new(Environment::from(closure)));
-                for (param, argument) in params.iter().zip(arguments.iter()) {
-                    environment.borrow_mut().define(&param.literal.as_ref().unwrap().to_string(), argument.clone());
+            Function::Native { .. } | Function::NativeMethod { .. } if !named.is_empty() => {
+                let (name, _) = &named[0];
+                Err(Error::wrong_arity(
+                    format!("Unexpected argument '{}'.", name),
+                    Some(name.clone()),
+                ))
+            }
+            Function::Native { body, .. } => body(interpreter, arguments)
+                .map_err(|e| ControlFlow::Error(e.located(paren))),
+            Function::NativeMethod { body, this, .. } => {
+                let this = this.as_deref().unwrap_or(&Value::Null);
+                body(interpreter, this, arguments).map_err(|e| ControlFlow::Error(e.located(paren)))
+            }
+            // nil once the generator is done
+            Function::GeneratorNext { generator } => {
+                Ok(Generator::next(generator, interpreter)?.unwrap_or(Value::Null))
+            }
+            Function::Custom { params, body , closure, module, is_initializer, .. } => {
+                let environment = gc::alloc(Environment::from(closure));
+                let mut positional = arguments.iter();
+                let mut named: Vec<&(Token, Value)> = named.iter().collect();
+
+                // positional arguments first, then named ones, then defaults evaluated in
+                // the scope of the parameters before them
+                for param in params.iter() {
+                    let name = param.name.to_string();
+                    let by_name = named.iter().position(|(n, _)| n.to_string() == name);
+                    let value = if param.rest {
+                        Value::list(positional.by_ref().cloned().collect())
+                    } else if let Some(argument) = positional.next() {
+                        if let Some(i) = by_name {
+                            return Err(Error::wrong_arity(
+                                format!("Argument '{}' is given more than once.", name),
+                                Some(named[i].0.clone()),
+                            ));
+                        }
+                        argument.clone()
+                    } else if let Some(i) = by_name {
+                        named.remove(i).1.clone()
+                    } else if let Some(default) = &param.default {
+                        interpreter.evaluate_in(default, Rc::clone(&environment), module)?
+                    } else {
+                        return Err(Error::wrong_arity(
+                            format!("Missing argument '{}'.", name),
+                            paren.cloned(),
+                        ));
+                    };
+                    environment.borrow_mut().define(&name, value);
+                }
+
+                if positional.next().is_some() {
+                    return Err(Error::wrong_arity(
+                        format!("Expected {} arguments but got {}.", self.arity(), arguments.len()),
+                        paren.cloned(),
+                    ));
+                }
+                if let Some((name, _)) = named.first() {
+                    return Err(Error::wrong_arity(
+                        format!("Unexpected argument '{}'.", name),
+                        Some(name.clone()),
+                    ));
                 }
 
                 if *is_initializer {
                     return Environment::get_at(Rc::clone(closure), 0, "this");
                 }
 
-                match interpreter.execute_block(&body, environment) {
+                // the body runs as `next()` is called on the generator
+                if body.iter().any(Stmt::yields) {
+                    let generator = Generator::new(Rc::clone(body), environment, module);
+                    return Ok(Value::Generator(Rc::new(RefCell::new(generator))));
+                }
+
+                match interpreter.execute_function(body, environment, module) {
                     Ok(_) => Ok(Value::Null),
                     Err(ControlFlow::Runtime(RuntimeControl::Return(value))) => {
                         if *is_initializer {
@@ -63,11 +221,93 @@ impl LoxCallable for Function {
             },
         }
     }
+}
+
+// How many arguments a callable accepts, `max` is `None` when a rest parameter takes any number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exact(n: usize) -> Self {
+        Self { min: n, max: Some(n) }
+    }
+
+    pub fn at_least(n: usize) -> Self {
+        Self { min: n, max: None }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
 
-     fn arity(&self) -> usize {
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+impl Trace for Function {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Function::Custom { closure, .. } => tracer.edge(closure),
+            Function::NativeMethod { this: Some(this), .. } => this.trace(tracer),
+            Function::GeneratorNext { generator } => tracer.shared(generator),
+            // what a Rust closure captures can't be seen, it stays alive
+            Function::Native { .. } | Function::NativeMethod { .. } => {}
+        }
+    }
+}
+
+// Functions are the same when they are the same declaration closed over the same scope, or the
+// same method bound to the same instance.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Native { body: a, .. }, Self::Native { body: b, .. }) => Rc::ptr_eq(a, b),
+            (Self::Custom { body: a, closure: c, .. }, Self::Custom { body: b, closure: d, .. }) => {
+                Rc::ptr_eq(a, b) && (Rc::ptr_eq(c, d) || Environment::same_binding(c, d))
+            }
+            (
+                Self::NativeMethod { body: a, this: c, .. },
+                Self::NativeMethod { body: b, this: d, .. },
+            ) => Rc::ptr_eq(a, b) && c == d,
+            (Self::GeneratorNext { generator: a }, Self::GeneratorNext { generator: b }) => {
+                Rc::ptr_eq(a, b)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Function {}
+
+impl Hash for Function {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let address = match self {
+            Self::Native { body, .. } => Rc::as_ptr(body) as *const (),
+            Self::Custom { body, .. } => Rc::as_ptr(body) as *const (),
+            Self::NativeMethod { body, .. } => Rc::as_ptr(body) as *const (),
+            Self::GeneratorNext { generator } => Rc::as_ptr(generator) as *const (),
+        };
+        address.hash(state);
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Function::Native { arity, .. } => *arity,
-            Function::Custom { params, .. } => params.len(),
+            Self::Custom { name, .. } => write!(f, "<fn {}>", name),
+            Self::Native { .. } | Self::NativeMethod { .. } | Self::GeneratorNext { .. } => {
+                write!(f, "<native fn>")
+            }
         }
     }
 }
@@ -75,8 +315,10 @@ impl LoxCallable for Function {
 impl Debug for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Native { arity, body } => f.debug_struct("Native").field("arity", arity).field("body", body).finish(),
-            Self::Custom { params, body, ..  } => f.debug_struct("Custom").field("params", params).field("body", body).finish(),
+            Self::Native { arity, .. } => f.debug_struct("Native").field("arity", arity).finish(),
+            Self::Custom { name, params, body, ..  } => f.debug_struct("Custom").field("name", name).field("params", params).field("body", body).finish(),
+            Self::NativeMethod { arity, .. } => f.debug_struct("NativeMethod").field("arity", arity).finish(),
+            Self::GeneratorNext { .. } => f.debug_struct("GeneratorNext").finish(),
         }
     }
 }
\ No newline at end of file
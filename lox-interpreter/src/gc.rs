@@ -0,0 +1,197 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+};
+
+use crate::Value;
+
+// Values are reference counted, which frees everything but cycles: an instance stored in its own
+// field, or a closure stored in the scope it captures. The collector finds those by trial
+// deletion. Every object that can close a cycle is registered when it is allocated; the
+// references between them are counted, and an object with more references than that is held
+// from outside, by a variable of the interpreter, the Rust stack or the host. Whatever none of
+// those reach is only kept alive by cycles, and emptying it breaks them.
+//
+// The heap is per thread, shared by every interpreter on it, like the values themselves.
+
+// collections run once this many objects were allocated since the last one, or twice as many
+// as survived it
+const MIN_THRESHOLD: usize = 10_000;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+#[derive(Default)]
+struct Heap {
+    objects: Vec<Weak<RefCell<dyn Collect>>>,
+    // objects alive after the last collection
+    survivors: usize,
+    stats: GcStats,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GcStats {
+    // estimated: the size of the objects themselves, not of the strings and tables they own
+    pub bytes_allocated: usize,
+    pub collections: usize,
+    // objects freed by collections, the ones reference counting couldn't free
+    pub collected: usize,
+}
+
+// Reports the references a value holds to objects that can be part of a cycle
+pub(crate) trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+// An object the collector tracks, `clear` drops the references it holds
+pub(crate) trait Collect: Trace {
+    fn clear(&mut self);
+}
+
+pub(crate) struct Tracer<'a> {
+    index: &'a HashMap<usize, usize>,
+    edges: Vec<usize>,
+}
+
+impl Tracer<'_> {
+    pub fn edge<T: Trace + ?Sized>(&mut self, object: &Rc<RefCell<T>>) {
+        match self.index.get(&address(object)) {
+            Some(&i) => self.edges.push(i),
+            None => self.shared(object),
+        }
+    }
+
+    // Something held behind an `Rc` that isn't tracked belongs to its only owner, its references
+    // are followed as if they were the owner's. Once it's shared they count as outside ones,
+    // which keeps what they reach alive.
+    pub fn shared<T: Trace + ?Sized>(&mut self, value: &Rc<T>) {
+        if Rc::strong_count(value) == 1 {
+            value.trace(self);
+        }
+    }
+}
+
+// a value being changed can't be read, it is treated as held from outside like the above
+impl<T: Trace + ?Sized> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Ok(value) = self.try_borrow() {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl Trace for Vec<Value> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl Collect for Vec<Value> {
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+impl Trace for HashMap<String, Value> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.values() {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl Collect for HashMap<String, Value> {
+    fn clear(&mut self) {
+        HashMap::clear(self);
+    }
+}
+
+pub(crate) fn alloc<T: Collect + 'static>(value: T) -> Rc<RefCell<T>> {
+    let object = Rc::new(RefCell::new(value));
+    let weak: Weak<RefCell<T>> = Rc::downgrade(&object);
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(weak as Weak<RefCell<dyn Collect>>);
+        heap.stats.bytes_allocated += mem::size_of::<RefCell<T>>() + 2 * mem::size_of::<usize>();
+    });
+    object
+}
+
+// whether enough was allocated since the last collection to run one: the registry, dead objects
+// included, is past the threshold
+pub(crate) fn due() -> bool {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        heap.objects.len() >= MIN_THRESHOLD.max(2 * heap.survivors)
+    })
+}
+
+// Frees the objects only cycles keep alive and returns how many there were
+pub(crate) fn collect() -> usize {
+    // the heap isn't borrowed while clearing, dropping values may allocate
+    let objects: Vec<Rc<RefCell<dyn Collect>>> = HEAP
+        .with(|heap| mem::take(&mut heap.borrow_mut().objects))
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| (address(object), i))
+        .collect();
+
+    let mut edges = Vec::with_capacity(objects.len());
+    let mut internal = vec![0; objects.len()];
+    for object in &objects {
+        let mut tracer = Tracer { index: &index, edges: Vec::new() };
+        object.trace(&mut tracer);
+        for &i in &tracer.edges {
+            internal[i] += 1;
+        }
+        edges.push(tracer.edges);
+    }
+
+    // roots have references from outside the heap, `objects` holds one more of its own
+    let mut reachable = vec![false; objects.len()];
+    let mut pending: Vec<usize> = (0..objects.len())
+        .filter(|&i| Rc::strong_count(&objects[i]) - 1 > internal[i])
+        .collect();
+    while let Some(i) = pending.pop() {
+        if !mem::replace(&mut reachable[i], true) {
+            pending.extend(&edges[i]);
+        }
+    }
+
+    for (object, reachable) in objects.iter().zip(reachable) {
+        if !reachable && let Ok(mut object) = object.try_borrow_mut() {
+            object.clear();
+        }
+    }
+
+    let weak: Vec<_> = objects.iter().map(Rc::downgrade).collect();
+    drop(objects);
+    let survivors: Vec<_> = weak.iter().filter(|w| w.strong_count() > 0).cloned().collect();
+    let collected = weak.len() - survivors.len();
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.survivors = survivors.len();
+        heap.objects.extend(survivors);
+        heap.stats.collections += 1;
+        heap.stats.collected += collected;
+    });
+    collected
+}
+
+pub(crate) fn stats() -> GcStats {
+    HEAP.with(|heap| heap.borrow().stats)
+}
+
+fn address<T: ?Sized>(object: &Rc<RefCell<T>>) -> usize {
+    Rc::as_ptr(object) as *const () as usize
+}
@@ -0,0 +1,267 @@
+use std::{cell::RefCell, rc::Rc};
+
+use lox_syntax::{Expr, Stmt, Token};
+
+use crate::{
+    environment::Environment,
+    errors::{ControlFlow, Error, ResultExec, RuntimeControl},
+    gc::{self, Trace, Tracer},
+    module::Module,
+    Interpreter, Value,
+};
+
+// The suspended body of a function containing `yield`. The interpreter runs statements with Rust
+// recursion, so the generator keeps the blocks and loops around the current yield as explicit
+// frames and steps through them itself. Statements without a yield inside run as usual.
+#[derive(Debug)]
+pub struct Generator {
+    frames: Vec<Frame>,
+    module: Rc<Module>,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Block {
+        statements: Rc<Vec<Stmt>>,
+        next: usize,
+        environment: Rc<RefCell<Environment>>,
+    },
+    While {
+        condition: Expr,
+        body: Stmt,
+        environment: Rc<RefCell<Environment>>,
+    },
+    ForIn {
+        name: Token,
+        iteration: Iteration,
+        body: Stmt,
+        environment: Rc<RefCell<Environment>>,
+    },
+}
+
+impl Generator {
+    pub fn new(body: Rc<Vec<Stmt>>, environment: Rc<RefCell<Environment>>, module: &Rc<Module>) -> Self {
+        Self {
+            frames: vec![Frame::Block {
+                statements: body,
+                next: 0,
+                environment,
+            }],
+            module: Rc::clone(module),
+        }
+    }
+
+    // Runs the body up to the next `yield` and returns its value, `None` once the body is done.
+    pub fn next(generator: &Rc<RefCell<Generator>>, interpreter: &mut Interpreter) -> ResultExec<Option<Value>> {
+        let mut generator = generator
+            .try_borrow_mut()
+            .map_err(|_| Error::invalid_context("Generator is already running.", None))?;
+
+        let result = generator.resume(interpreter);
+        if !matches!(result, Ok(Some(_))) {
+            // finished, returned or failed: it never runs again
+            generator.frames.clear();
+        }
+        result
+    }
+
+    fn resume(&mut self, interpreter: &mut Interpreter) -> ResultExec<Option<Value>> {
+        while let Some(frame) = self.frames.last_mut() {
+            let result = match frame {
+                Frame::Block {
+                    statements,
+                    next,
+                    environment,
+                } => {
+                    let Some(stmt) = statements.get(*next) else {
+                        self.frames.pop();
+                        continue;
+                    };
+                    *next += 1;
+                    let (stmt, environment) = (stmt.clone(), Rc::clone(environment));
+                    self.step(interpreter, &stmt, environment)
+                }
+                Frame::While {
+                    condition,
+                    body,
+                    environment,
+                } => {
+                    let environment = Rc::clone(environment);
+                    let body = body.clone();
+                    let value = interpreter.evaluate_in(condition, Rc::clone(&environment), &self.module)?;
+                    if interpreter.is_truthy(&value) {
+                        self.push(body, environment);
+                    } else {
+                        self.frames.pop();
+                    }
+                    continue;
+                }
+                Frame::ForIn {
+                    name,
+                    iteration,
+                    body,
+                    environment,
+                } => {
+                    let (item, body) = (iteration.next(interpreter, name), body.clone());
+                    let name = name.to_string();
+                    let environment = gc::alloc(Environment::from(environment));
+                    match item? {
+                        Some(value) => {
+                            environment.borrow_mut().define(&name, value);
+                            self.push(body, environment);
+                        }
+                        None => {
+                            self.frames.pop();
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            match result {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) => {}
+                // leave the innermost loop with every block inside it
+                Err(ControlFlow::Runtime(RuntimeControl::Break)) => {
+                    while let Some(Frame::Block { .. }) = self.frames.pop() {}
+                }
+                Err(ControlFlow::Runtime(RuntimeControl::Return(_))) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Runs one statement of the current block, or enters it when a `yield` is inside.
+    fn step(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+        environment: Rc<RefCell<Environment>>,
+    ) -> ResultExec<Option<Value>> {
+        if !stmt.yields() {
+            return interpreter.execute_in(stmt, environment, &self.module).map(|_| None);
+        }
+
+        match stmt {
+            Stmt::Yield { value, .. } => match value {
+                Some(value) => interpreter.evaluate_in(value, environment, &self.module).map(Some),
+                None => Ok(Some(Value::Null)),
+            },
+            Stmt::Block { statements } => {
+                self.frames.push(Frame::Block {
+                    statements: Rc::new(statements.clone()),
+                    next: 0,
+                    environment: gc::alloc(Environment::from(&environment)),
+                });
+                Ok(None)
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let value = interpreter.evaluate_in(condition, Rc::clone(&environment), &self.module)?;
+                if interpreter.is_truthy(&value) {
+                    self.push(then_branch.as_ref().clone(), environment);
+                } else if let Some(else_branch) = else_branch {
+                    self.push(else_branch.as_ref().clone(), environment);
+                }
+                Ok(None)
+            }
+            Stmt::While { condition, body } => {
+                self.frames.push(Frame::While {
+                    condition: condition.clone(),
+                    body: body.as_ref().clone(),
+                    environment,
+                });
+                Ok(None)
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                let value = interpreter.evaluate_in(iterable, Rc::clone(&environment), &self.module)?;
+                self.frames.push(Frame::ForIn {
+                    name: name.clone(),
+                    iteration: Iteration::over(value, name)?,
+                    body: body.as_ref().clone(),
+                    environment,
+                });
+                Ok(None)
+            }
+            _ => unreachable!("only blocks, ifs and loops contain a yield"),
+        }
+    }
+
+    // a single statement run in `environment`, the way an `if` or loop body is
+    fn push(&mut self, stmt: Stmt, environment: Rc<RefCell<Environment>>) {
+        self.frames.push(Frame::Block {
+            statements: Rc::new(vec![stmt]),
+            next: 0,
+            environment,
+        });
+    }
+}
+
+impl Trace for Generator {
+    fn trace(&self, tracer: &mut Tracer) {
+        for frame in &self.frames {
+            match frame {
+                Frame::Block { environment, .. } | Frame::While { environment, .. } => {
+                    tracer.edge(environment)
+                }
+                Frame::ForIn { iteration, environment, .. } => {
+                    match iteration {
+                        Iteration::List { items, .. } => tracer.edge(items),
+                        Iteration::Generator(generator) => tracer.shared(generator),
+                    }
+                    tracer.edge(environment);
+                }
+            }
+        }
+    }
+}
+
+// The state of a `for (var x in ...)` loop
+#[derive(Debug)]
+pub(crate) enum Iteration {
+    List {
+        items: Rc<RefCell<Vec<Value>>>,
+        next: usize,
+    },
+    Generator(Rc<RefCell<Generator>>),
+}
+
+impl Iteration {
+    // `name` is the loop variable, to locate the error when the value can't be iterated
+    pub fn over(value: Value, name: &Token) -> ResultExec<Self> {
+        match value {
+            Value::List(items) => Ok(Iteration::List { items, next: 0 }),
+            Value::Generator(generator) => Ok(Iteration::Generator(generator)),
+            _ => Err(Error::wrong_value_type(
+                "Can only iterate over lists and generators.",
+                Some(name.clone()),
+            )),
+        }
+    }
+
+    // `name` is the loop variable, resuming a generator counts as a call made there
+    pub fn next(&mut self, interpreter: &mut Interpreter, name: &Token) -> ResultExec<Option<Value>> {
+        match self {
+            Iteration::List { items, next } => {
+                let item = items.borrow().get(*next).cloned();
+                *next += 1;
+                Ok(item)
+            }
+            Iteration::Generator(generator) => {
+                interpreter.enter_call(Some(name))?;
+                let item = Generator::next(generator, interpreter);
+                interpreter.exit_call();
+                item
+            }
+        }
+    }
+}
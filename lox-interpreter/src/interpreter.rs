@@ -1,23 +1,72 @@
 use crate::{
-    class::Class,
+    class::{self, Class, Instance, Trait},
+    convert::IntoNative,
+    foreign::ForeignClass,
+    enums::{Constructor, Enum, Variant as EnumVariant},
     environment::Environment,
-    errors::{ControlFlow, Error, ResultExec, RuntimeControl},
-    function::Function,
+    errors::{ControlFlow, Error, ErrorKind, ResultExec, RuntimeControl},
+    function::{Arity, Function},
+    gc::{self, GcStats},
+    generator::Iteration,
+    limits::{Budget, Limits},
+    module::Module,
     value::Value,
+    Resolver,
+};
+use lox_syntax::{
+    parse_program, Expr, ExprVisitor, Lexer, MatchArm, Param, Pattern, Stmt, StmtVisitor,
+    Token, TokenType, Variant,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
 };
-use lox_syntax::{Expr, ExprVisitor, Stmt, StmtVisitor, Token, TokenType};
-use std::{cell::RefCell, collections::HashMap};
 use std::{ops::Deref, rc::Rc};
 
 pub trait LoxCallable {
     fn call(&self, interpreter: &mut Interpreter, arguments: &Vec<Value>) -> ResultExec<Value>;
-    fn arity(&self) -> usize;
+    fn arity(&self) -> Arity;
+
+    // Call with `name: value` arguments too, `paren` locates errors binding them. Only
+    // functions with named parameters accept them.
+    fn call_named(
+        &self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: &Vec<Value>,
+        named: &[(Token, Value)],
+    ) -> ResultExec<Value> {
+        match named.first() {
+            Some((name, _)) => Err(Error::wrong_arity(
+                format!("Unexpected argument '{}'.", name),
+                Some(name.clone()),
+            )),
+            None => self.call(interpreter, arguments),
+        }
+    }
 }
 
 pub struct Interpreter {
     environment: Rc<RefCell<Environment>>,
-    pub globals: Rc<RefCell<Environment>>,
-    locals: HashMap<String, usize>,
+    // natives, shared by every module
+    builtins: Rc<RefCell<Environment>>,
+    // module whose code is currently running
+    module: Rc<Module>,
+    // loaded modules by canonical path, and the chain of imports still running
+    modules: HashMap<PathBuf, Rc<Module>>,
+    loading: Vec<PathBuf>,
+    // whether `import` may read module files, hosts sandboxing a script turn it off
+    imports: bool,
+    // where `print` writes, where hosts report errors, and where natives read input from
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    input: Box<dyn BufRead>,
+    budget: Budget,
 }
 
 impl ExprVisitor<ResultExec<Value>> for Interpreter {
@@ -25,35 +74,61 @@ impl ExprVisitor<ResultExec<Value>> for Interpreter {
         match expr {
             // TODO: handle comma operator
             Expr::Literal { value } => Ok(Value::from(value.clone())),
-            Expr::Grouping { expression } => self.visit_grouping_expr(&expression),
+            Expr::Grouping { expression } => self.visit_grouping_expr(expression),
             Expr::Binary {
                 left,
                 operator,
                 right,
             } => self.visit_binary_expr(left, operator, right),
             Expr::Unary { operator, right } => self.visit_unary_expr(operator, right),
+            Expr::Postfix { target, operator } => self.visit_postfix_expr(target, operator),
             Expr::Variable { name } => self.visit_var_expr(name),
             Expr::Assign { name, value } => self.visit_assign_expr(name, value),
+            Expr::AssignPattern { pattern, value } => {
+                let value = self.evaluate(value)?;
+                let mut bindings = Vec::new();
+                self.destructure(pattern, &value, &mut bindings)?;
+                for (name, value) in bindings {
+                    self.assign_variable(&name, value)?;
+                }
+                Ok(value)
+            }
             Expr::Logical {
                 left,
                 operator,
                 right,
             } => self.visit_logical_expr(left, operator, right),
-            Expr::Call {
-                callee,
-                paren,
-                arguments,
-            } => self.visit_call_expr(callee, paren, arguments),
-            Expr::Get { object, name } => self.visit_get_expr(object, name),
+            Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } => {
+                Ok(self.evaluate_chain(expr)?.unwrap_or(Value::Null))
+            }
             Expr::Set {
                 object,
                 name,
                 value,
-            } => self.visit_set_expr(object, name, value),
+                operator,
+            } => self.visit_set_expr(object, name, value, operator),
             Expr::Super { keyword, method } => self.visit_super_expr(keyword, method),
             Expr::This { keyword } => self.look_up_var(keyword),
             Expr::Lambda { params, body } => self.visit_lambda_expr(params, body),
+            Expr::MethodRef { object, name } => self.visit_method_ref_expr(object, name),
+            Expr::List { elements, .. } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(Value::list(values))
+            }
+            Expr::Match {
+                keyword,
+                subject,
+                arms,
+            } => self.visit_match_expr(keyword, subject, arms),
             Expr::Comma { left, right } => self.visit_comma_expr(left, right),
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.visit_conditional_expr(condition, then_branch, else_branch),
         }
     }
 }
@@ -63,8 +138,25 @@ impl StmtVisitor<ResultExec<()>> for Interpreter {
         match stmt {
             Stmt::Print { expression } => self.visit_print_stmt(expression),
             Stmt::Expression { expression } => self.visit_expr_stmt(expression),
-            Stmt::Var { name, initializer } => self.visit_var_stmt(name, initializer),
-            Stmt::Function { name, params, body } => self.visit_function_stmt(name, params, body),
+            Stmt::Var {
+                name, initializer, ..
+            } => self.visit_var_stmt(name, initializer),
+            Stmt::VarPattern {
+                pattern,
+                initializer,
+                ..
+            } => {
+                let value = self.evaluate(initializer)?;
+                let mut bindings = Vec::new();
+                self.destructure(pattern, &value, &mut bindings)?;
+                for (name, value) in bindings {
+                    self.environment.borrow_mut().define(&name.to_string(), value);
+                }
+                Ok(())
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => self.visit_function_stmt(name, params, body),
             Stmt::Return { keyword, value } => self.visit_return_stmt(keyword, value),
             Stmt::Block { statements } => self.visit_block_stmt(statements),
             Stmt::If {
@@ -74,11 +166,43 @@ impl StmtVisitor<ResultExec<()>> for Interpreter {
             } => self.visit_if_stmt(condition, then_branch, else_branch),
             Stmt::While { condition, body } => self.visit_while_stmt(condition, body),
             Stmt::Break => self.visit_break_stmt(),
+            Stmt::Trait { name, methods } => self.visit_trait_stmt(name, methods),
+            Stmt::Enum { name, variants } => self.visit_enum_stmt(name, variants),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => self.visit_for_in_stmt(name, iterable, body),
+            Stmt::Yield { keyword, .. } => Err(Error::invalid_context(
+                "Can't yield from inside an expression.",
+                Some(keyword.clone()),
+            )),
+            Stmt::Import { path, alias, names } => self.visit_import_stmt(path, alias, names),
+            Stmt::Export { name, declaration } => {
+                self.execute(declaration)?;
+                self.module.export(name);
+                Ok(())
+            }
             Stmt::Class {
                 name,
                 superclass,
+                traits,
+                methods,
+                getters,
+                setters,
+                static_methods,
+                static_fields,
+                ..
+            } => self.visit_class_stmt(
+                name,
+                superclass,
+                traits,
                 methods,
-            } => self.visit_class_stmt(name, methods, superclass),
+                getters,
+                setters,
+                static_methods,
+                static_fields,
+            ),
             /*_ => Err(Error::unrecognized_stmt(
                 format!("Unrecognized stmt: {:?}.", stmt),
                 None,
@@ -87,18 +211,105 @@ impl StmtVisitor<ResultExec<()>> for Interpreter {
     }
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
-        let globals = Rc::new(RefCell::new(Environment::new()));
+        let builtins = gc::alloc(Environment::new());
+        let module = Rc::new(Module::new(None, &builtins));
 
         Self {
-            environment: Rc::clone(&globals),
-            globals: Rc::clone(&globals),
-            locals: HashMap::new(),
+            environment: module.globals(),
+            builtins,
+            module,
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            imports: true,
+            output: Box::new(io::stdout()),
+            error_output: Box::new(io::stderr()),
+            input: Box::new(BufReader::new(io::stdin())),
+            budget: Budget::new(Limits::default()),
         }
     }
 
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.set_limits(limits);
+    }
+
+    pub fn set_imports(&mut self, allowed: bool) {
+        self.imports = allowed;
+    }
+
+    // Setting the flag, from any thread, stops the running script with an `Interrupted` error.
+    // It stays set, and stops every later run too, until it is cleared.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.budget.cancel_flag()
+    }
+
+    // Frees the values only reference cycles keep alive, and returns how many there were. It runs
+    // by itself as a script allocates, this is for hosts that know when it's a good time.
+    pub fn collect_garbage(&mut self) -> usize {
+        gc::collect()
+    }
+
+    // for the heap of the thread, shared by the interpreters on it
+    pub fn gc_stats(&self) -> GcStats {
+        gc::stats()
+    }
+
+    pub(crate) fn enter_call(&mut self, paren: Option<&Token>) -> ResultExec<()> {
+        self.budget.enter_call(paren)
+    }
+
+    pub(crate) fn exit_call(&mut self) {
+        self.budget.exit_call();
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn set_error_output(&mut self, error_output: Box<dyn Write>) {
+        self.error_output = error_output;
+    }
+
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = input;
+    }
+
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    pub fn error_output(&mut self) -> &mut dyn Write {
+        &mut self.error_output
+    }
+
+    // the next line of the input without its line ending, `None` at the end
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(trimmed);
+        Ok(Some(line))
+    }
+
+    // Imports in the script are resolved relative to its directory.
+    pub fn set_script_path(&mut self, path: &str) -> std::io::Result<()> {
+        let path = fs::canonicalize(path)?;
+        self.module.set_path(path.clone());
+        self.loading = vec![path];
+        Ok(())
+    }
+
     pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        self.budget.start();
         for stmt in statements {
             if let Err(ControlFlow::Error(e)) = self.execute(stmt) {
                 return Err(e);
@@ -108,42 +319,147 @@ impl Interpreter {
         Ok(())
     }
 
-    pub fn set_global_fn(&mut self, name: &str, arity: usize, func: fn(&Vec<Value>) -> Value) {
+    // Like `interpret`, giving back the value of the last statement when it is an expression
+    pub fn interpret_value(&mut self, statements: &[Stmt]) -> Result<Value, Error> {
+        let (last, statements) = match statements.split_last() {
+            Some((Stmt::Expression { expression }, rest)) => (Some(expression), rest),
+            _ => (None, statements),
+        };
+        self.interpret(statements)?;
+
+        match last.map(|expression| self.evaluate(expression)) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(ControlFlow::Error(e))) => Err(e),
+            Some(Err(ControlFlow::Runtime(_))) | None => Ok(Value::Null),
+        }
+    }
+
+    // Defines a function written in Rust, visible from every module. An error it returns
+    // without a location is reported at the call.
+    pub fn set_global_fn(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, Error> + 'static,
+    ) {
         let callable = Value::Callable(Function::Native {
             arity,
-            body: Box::new(func),
+            body: Rc::new(func),
         });
-        self.globals.borrow_mut().define(name, callable);
+        self.builtins.borrow_mut().define(name, callable);
+    }
+
+    // Defines a plain Rust function, `fn add(a: f64, b: f64) -> f64`, as a native. Its arity is
+    // the number of parameters and the arguments are converted to their types before the call.
+    pub fn set_typed_fn<Args>(&mut self, name: &str, func: impl IntoNative<Args>) {
+        let arity = func.arity();
+        self.set_global_fn(name, arity, func.into_native(name));
+    }
+
+    // Defines a class implemented in Rust, visible from every module. The class is returned to
+    // create instances around existing Rust values with `ForeignClass::instance`.
+    pub fn set_global_class<T: Any>(&mut self, class: ForeignClass<T>) -> Value {
+        let name = class.name().to_string();
+        let class = class.build();
+        self.builtins.borrow_mut().define(&name, class.clone());
+        class
+    }
+
+    // Calls a function or class from Rust, natives use it to call back into the script
+    pub fn call_value(&mut self, callee: &Value, arguments: &[Value]) -> Result<Value, Error> {
+        let callee = Self::call_target(callee.clone());
+        let Some(callable) = Self::callable(&callee) else {
+            return Err(Error::new(
+                ErrorKind::WrongValueType("Can only call functions and classes.".to_string()),
+                None,
+            ));
+        };
+        if !callable.arity().accepts(arguments.len()) {
+            return Err(Error::new(
+                ErrorKind::WrongArity(format!(
+                    "Expected {} arguments but got {}.",
+                    callable.arity(),
+                    arguments.len()
+                )),
+                None,
+            ));
+        }
+
+        self.budget.start();
+        match callable.call(self, &arguments.to_vec()) {
+            Ok(value) => Ok(value),
+            Err(ControlFlow::Error(e)) => Err(e),
+            Err(ControlFlow::Runtime(_)) => unreachable!("break and return never leave a function"),
+        }
+    }
+
+    // The text `print` shows for `value`, from the `__str__` methods of instances
+    pub fn display(&mut self, value: &Value) -> Result<String, Error> {
+        self.budget.start();
+        match self.stringify(value) {
+            Ok(text) => Ok(text),
+            Err(ControlFlow::Error(e)) => Err(e),
+            Err(ControlFlow::Runtime(_)) => unreachable!("break and return never leave a function"),
+        }
+    }
+
+    // calling an instance calls its `__call__` method
+    fn call_target(value: Value) -> Value {
+        if let Value::Instance(instance) = &value
+            && let Some(call) = instance.borrow().method("__call__")
+        {
+            return Value::Callable(call);
+        }
+        value
+    }
+
+    fn callable(value: &Value) -> Option<&dyn LoxCallable> {
+        match value {
+            Value::Callable(f) => Some(f),
+            Value::Class(c) => Some(c.deref()),
+            Value::Constructor(c) => Some(c),
+            _ => None,
+        }
     }
 
     pub fn resolve(&mut self, name: &Token, depth: usize) {
-        self.locals.insert(name.to_string(), depth);
+        self.module.resolve(name, depth);
+    }
+
+    pub(crate) fn module(&self) -> Rc<Module> {
+        Rc::clone(&self.module)
     }
 
     // ----- Expression interpreting methods ----
 
     fn evaluate(&mut self, expr: &Expr) -> ResultExec<Value> {
+        self.budget.step()?;
         expr.accept(self)
     }
 
     fn visit_assign_expr(&mut self, name: &Token, value: &Expr) -> ResultExec<Value> {
         let value = self.evaluate(value)?;
+        self.assign_variable(name, value.clone())?;
+        Ok(value)
+    }
 
-        let distance = self.locals.get(&name.to_string());
+    fn assign_variable(&mut self, name: &Token, value: Value) -> ResultExec<()> {
+        let distance = self.module.depth(name);
         if let Some(distance) = distance {
             Environment::assign_at(
                 self.environment.clone(),
-                *distance,
+                distance,
                 &name.to_string(),
                 value.clone(),
             )
         } else {
-            self.globals
+            self.module
+                .globals()
                 .borrow_mut()
                 .assign(&name.to_string(), value.clone())?;
         }
 
-        return Ok(value);
+        Ok(())
     }
 
     fn visit_var_expr(&self, name: &Token) -> ResultExec<Value> {
@@ -163,8 +479,12 @@ impl Interpreter {
                 Ok(Value::Number(-value))
             }
             TokenType::BANG => Ok(Value::Bool(!self.is_truthy(&right))),
+            TokenType::TILDE => {
+                let value = self.check_integer_operand(&right)?;
+                Ok(Value::Number(!value as f32))
+            }
             _ => Err(Error::unrecognized_opt(
-                format!("Unknown unary operator: {}", operator.to_string()),
+                format!("Unknown unary operator: {}", operator),
                 Some(operator.clone()),
             )),
         }
@@ -179,6 +499,27 @@ impl Interpreter {
         let left = self.evaluate(left)?;
         let right = self.evaluate(right)?;
 
+        self.binary_op(left, operator, right)
+    }
+
+    fn binary_op(&mut self, left: Value, operator: &Token, right: Value) -> ResultExec<Value> {
+        if let Value::Instance(instance) = &left
+            && let Some(name) = class::operator_method(&operator.token_type)
+        {
+            let method = instance.borrow().method(name);
+            if let Some(method) = method {
+                return method.invoke(self, Some(operator), &[right], &[]);
+            }
+        }
+        if let Value::Instance(instance) = &right
+            && let Some(name) = class::reflected_method(&operator.token_type)
+        {
+            let method = instance.borrow().method(name);
+            if let Some(method) = method {
+                return method.invoke(self, Some(operator), &[left], &[]);
+            }
+        }
+
         match operator.token_type {
             TokenType::MINUS => {
                 let (l, r) = self.check_number_operands(&left, &right)?;
@@ -192,6 +533,49 @@ impl Interpreter {
                 let (l, r) = self.check_number_operands(&left, &right)?;
                 Ok(Value::Number(l * r))
             }
+            TokenType::PERCENT => {
+                let (l, r) = self.check_number_operands(&left, &right)?;
+                Ok(Value::Number(l % r))
+            }
+            TokenType::STAR_STAR => {
+                let (l, r) = self.check_number_operands(&left, &right)?;
+                Ok(Value::Number(l.powf(r)))
+            }
+            TokenType::TILDE_SLASH => {
+                let (l, r) = self.check_integer_operands(&left, &right)?;
+                if r == 0 {
+                    return Err(Error::wrong_value_type(
+                        "Integer division by zero.",
+                        Some(operator.clone()),
+                    ));
+                }
+                Ok(Value::Number(l.wrapping_div(r) as f32))
+            }
+            TokenType::AMPERSAND => {
+                let (l, r) = self.check_integer_operands(&left, &right)?;
+                Ok(Value::Number((l & r) as f32))
+            }
+            TokenType::PIPE => {
+                let (l, r) = self.check_integer_operands(&left, &right)?;
+                Ok(Value::Number((l | r) as f32))
+            }
+            TokenType::CARET => {
+                let (l, r) = self.check_integer_operands(&left, &right)?;
+                Ok(Value::Number((l ^ r) as f32))
+            }
+            TokenType::LESS_LESS | TokenType::GREATER_GREATER => {
+                let (l, r) = self.check_integer_operands(&left, &right)?;
+                let shifted = match operator.token_type {
+                    TokenType::LESS_LESS => u32::try_from(r).ok().and_then(|r| l.checked_shl(r)),
+                    _ => u32::try_from(r).ok().and_then(|r| l.checked_shr(r)),
+                };
+                shifted.map(|n| Value::Number(n as f32)).ok_or_else(|| {
+                    Error::wrong_value_type(
+                        "Shift amount must be between 0 and 31.",
+                        Some(operator.clone()),
+                    )
+                })
+            }
             TokenType::PLUS => match (left, right) {
                 (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
                 (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
@@ -216,10 +600,10 @@ impl Interpreter {
                 let (l, r) = self.check_number_operands(&left, &right)?;
                 Ok(Value::Bool(l <= r))
             }
-            TokenType::EQUAL_EQUAL => Ok(Value::Bool(self.is_equal(&left, &right))),
-            TokenType::BANG_EQUAL => Ok(Value::Bool(!self.is_equal(&left, &right))),
+            TokenType::EQUAL_EQUAL => Ok(Value::Bool(self.is_equal(&left, &right, Some(operator))?)),
+            TokenType::BANG_EQUAL => Ok(Value::Bool(!self.is_equal(&left, &right, Some(operator))?)),
             _ => Err(Error::unrecognized_opt(
-                format!("Unknown binary operator operator: {}", operator.to_string()),
+                format!("Unknown binary operator operator: {}", operator),
                 Some(operator.clone()),
             )),
         }
@@ -231,7 +615,7 @@ impl Interpreter {
         operator: &Token,
         right: &Box<Expr>,
     ) -> ResultExec<Value> {
-        let left = self.evaluate(&left)?;
+        let left = self.evaluate(left)?;
 
         match operator.token_type {
             TokenType::OR => {
@@ -244,6 +628,11 @@ impl Interpreter {
                     return Ok(left);
                 }
             }
+            TokenType::QUESTION_QUESTION => {
+                if !matches!(left, Value::Null) {
+                    return Ok(left);
+                }
+            }
             _ => {
                 return Err(Error::unexpected_opt(
                     format!("Expect logical operator, got {}.", operator.token_type),
@@ -252,84 +641,274 @@ impl Interpreter {
             }
         }
 
-        self.evaluate(&right)
+        self.evaluate(right)
+    }
+
+    // Evaluates a chain of property accesses, calls and indexing. `None` means a `?.` found nil
+    // and skipped the rest of the chain: `a?.b.c()` is nil as a whole when `a` is.
+    fn evaluate_chain(&mut self, expr: &Expr) -> ResultExec<Option<Value>> {
+        match expr {
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => {
+                let Some(object) = self.evaluate_chain(object)? else {
+                    return Ok(None);
+                };
+                if *optional && matches!(object, Value::Null) {
+                    return Ok(None);
+                }
+                self.get_property(object, name).map(Some)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                named_arguments,
+            } => {
+                let Some(callee) = self.evaluate_chain(callee)? else {
+                    return Ok(None);
+                };
+                self.visit_call_expr(callee, paren, arguments, named_arguments).map(Some)
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let Some(object) = self.evaluate_chain(object)? else {
+                    return Ok(None);
+                };
+                self.visit_index_expr(object, bracket, index).map(Some)
+            }
+            _ => self.evaluate(expr).map(Some),
+        }
     }
 
     fn visit_call_expr(
         &mut self,
-        callee_expr: &Expr,
+        callee: Value,
         paren: &Token,
         arg_exprs: &Vec<Expr>,
+        named_exprs: &Vec<(Token, Expr)>,
     ) -> ResultExec<Value> {
-        let callee = self.evaluate(callee_expr)?;
-        let callable: &dyn LoxCallable = match callee {
-            Value::Callable(ref f) => f,
-            Value::Class(ref c) => c.deref(),
-            _ => return Err(Error::not_callable(paren.to_string(), Some(paren.clone()))),
+        let callee = Self::call_target(callee);
+        let Some(callable) = Self::callable(&callee) else {
+            return Err(Error::not_callable(paren.to_string(), Some(paren.clone())));
         };
 
         let mut args = Vec::with_capacity(arg_exprs.len());
         for argument in arg_exprs {
             args.push(self.evaluate(argument)?);
         }
+        let mut named = Vec::with_capacity(named_exprs.len());
+        for (name, argument) in named_exprs {
+            named.push((name.clone(), self.evaluate(argument)?));
+        }
+
+        let count = args.len() + named.len();
+        if !callable.arity().accepts(count) {
+            return Err(Error::wrong_arity(
+                format!("Expected {} arguments but got {}.", callable.arity(), count),
+                Some(paren.clone()),
+            ));
+        }
 
-        callable.call(self, &args)
+        callable.call_named(self, paren, &args, &named)
     }
 
-    fn visit_get_expr(&mut self, object: &Box<Expr>, name: &Token) -> ResultExec<Value> {
-        let object = self.evaluate(&object)?;
+    fn get_property(&mut self, object: Value, name: &Token) -> ResultExec<Value> {
         match object {
-            Value::Instance(i) => i.borrow().get(name),
+            Value::Instance(i) => {
+                let getter = i.borrow().getter(name);
+                match getter {
+                    Some(getter) => getter.call(self, &Vec::new()),
+                    None => i.borrow().get(name),
+                }
+            }
+            Value::Class(c) => Class::get(&c, name),
+            Value::Module(m) => m.get(name),
+            Value::Enum(e) => Enum::get(&e, name),
+            Value::EnumValue(v) => v.field(&name.to_string()).ok_or_else(|| {
+                Error::undefined_var(format!("Undefined field '{}'.", name), Some(name.clone()))
+            }),
+            Value::Generator(generator) if name.to_string() == "next" => {
+                Ok(Value::Callable(Function::GeneratorNext { generator }))
+            }
             _ => Err(Error::unexpected_expr(
-                "Only instances have properties",
+                "Only instances and classes have properties",
                 Some(name.clone()),
             )),
         }
     }
 
+    fn visit_index_expr(&mut self, object: Value, bracket: &Token, index: &Expr) -> ResultExec<Value> {
+        let index = self.evaluate(index)?;
+        match &object {
+            Value::List(list) => {
+                let i = as_integer(&index).ok_or_else(|| {
+                    Error::wrong_value_type("List index must be an integer.", Some(bracket.clone()))
+                })?;
+                let item = usize::try_from(i).ok().and_then(|i| list.borrow().get(i).cloned());
+                item.ok_or_else(|| {
+                    Error::wrong_value_type("List index out of range.", Some(bracket.clone()))
+                })
+            }
+            Value::Instance(instance) => {
+                let method = instance.borrow().method("__index__");
+                match method {
+                    Some(method) => method.invoke(self, Some(bracket), &[index], &[]),
+                    None => Err(Error::undefined_var(
+                        format!("Undefined method '__index__' to index {}.", object),
+                        Some(bracket.clone()),
+                    )),
+                }
+            }
+            _ => Err(Error::wrong_value_type(
+                "Only lists and instances can be indexed.",
+                Some(bracket.clone()),
+            )),
+        }
+    }
+
+    fn visit_method_ref_expr(&mut self, object: &Expr, name: &Token) -> ResultExec<Value> {
+        let key = name.to_string();
+        let method = match self.evaluate(object)? {
+            Value::Instance(i) => i.borrow().method(&key),
+            Value::Class(c) => {
+                if c.find_static_method(&key).is_none() && c.find_method(&key).is_some() {
+                    return Err(Error::invalid_context(
+                        format!("'{}' is an instance method, reference it through an instance.", key),
+                        Some(name.clone()),
+                    ));
+                }
+                Class::static_method(&c, &key)
+            }
+            _ => {
+                return Err(Error::unexpected_expr(
+                    "Only instances and classes have methods.",
+                    Some(name.clone()),
+                ));
+            }
+        };
+
+        method.map(Value::Callable).ok_or_else(|| {
+            Error::undefined_var(format!("Undefined method '{}'.", key), Some(name.clone()))
+        })
+    }
+
     fn visit_set_expr(
         &mut self,
         object: &Box<Expr>,
         name: &Token,
         value: &Box<Expr>,
+        operator: &Option<Token>,
     ) -> ResultExec<Value> {
-        let object = self.evaluate(&object)?;
+        let object = self.evaluate(object)?;
+        if !matches!(object, Value::Instance(_) | Value::Class(_)) {
+            return Err(Error::unexpected_expr(
+                "Only instances and classes have fields",
+                Some(name.clone()),
+            ));
+        }
+
+        let mut value = self.evaluate(value)?;
+        if let Some(operator) = operator {
+            let current = self.get_property(object.clone(), name)?;
+            value = self.binary_op(current, operator, value)?;
+        }
+
+        self.set_property(object, name, value.clone())?;
+        Ok(value)
+    }
 
+    fn set_property(&mut self, object: Value, name: &Token, value: Value) -> ResultExec<()> {
         match object {
             Value::Instance(i) => {
-                let value = self.evaluate(&value)?;
-                i.borrow_mut().set(name, &value);
-                Ok(value)
+                let setter = i.borrow().setter(name);
+                if let Some(setter) = setter {
+                    setter.call(self, &vec![value.clone()])?;
+                } else if i.borrow().has_getter(name) {
+                    return Err(Error::invalid_context(
+                        format!("Property '{}' has a getter but no setter.", name),
+                        Some(name.clone()),
+                    ));
+                } else {
+                    i.borrow().set(name, &value);
+                }
+            }
+            Value::Class(c) => c.set(name, &value),
+            _ => {
+                return Err(Error::unexpected_expr(
+                    "Only instances and classes have fields",
+                    Some(name.clone()),
+                ))
             }
-            _ => Err(Error::unexpected_expr(
-                "Only instances have fields",
-                Some(name.clone()),
-            )),
         }
+        Ok(())
     }
 
-    fn visit_super_expr(&self, keyword: &Token, method: &Token) -> ResultExec<Value> {
+    fn visit_postfix_expr(&mut self, target: &Expr, operator: &Token) -> ResultExec<Value> {
+        let one = Value::Number(1.0);
+        match target {
+            Expr::Variable { name } => {
+                let current = self.look_up_var(name)?;
+                let value = self.binary_op(current.clone(), operator, one)?;
+                self.assign_variable(name, value)?;
+                Ok(current)
+            }
+            Expr::Get { object, name, .. } => {
+                let object = self.evaluate(object)?;
+                let current = self.get_property(object.clone(), name)?;
+                let value = self.binary_op(current.clone(), operator, one)?;
+                self.set_property(object, name, value)?;
+                Ok(current)
+            }
+            _ => unreachable!("the parser only allows variables and properties"),
+        }
+    }
+
+    fn visit_super_expr(&mut self, keyword: &Token, method: &Token) -> ResultExec<Value> {
         let distance = self
-            .locals
-            .get(&keyword.to_string())
+            .module
+            .depth(keyword)
             .ok_or_else(|| Error::undefined_var("super not resolved", None))?;
 
-        let superclass_val = Environment::get_at(Rc::clone(&self.environment), *distance, "super")?;
+        let superclass_val = Environment::get_at(Rc::clone(&self.environment), distance, "super")?;
         let superclass = match superclass_val {
             Value::Class(ref c) => Rc::clone(c),
+            // a trait method composed into a class that has no superclass
+            Value::Null => {
+                return Err(Error::invalid_context(
+                    "Can't use 'super' in a class with no superclass.",
+                    Some(keyword.clone()),
+                ))
+            }
             _ => return Err(Error::wrong_value_type("Superclass is not a class", None)),
         };
 
         // "this" is always one level nearer than "super"
-        let instance_val = Environment::get_at(Rc::clone(&self.environment), *distance - 1, "this")?;
-        let instance = match instance_val {
-            Value::Instance(ref i) => Rc::clone(i),
+        let this = Environment::get_at(Rc::clone(&self.environment), distance - 1, "this")?;
+
+        // inside a static method `this` is the class, so `super` looks up static methods
+        let method_name = method.to_string();
+        let found = match this {
+            Value::Instance(_) => match superclass.find_getter(&method_name) {
+                Some(getter) => {
+                    let bound = getter
+                        .bind(this)
+                        .ok_or_else(|| Error::invalid_context("Bind returned None", None))?;
+                    return bound.call(self, &Vec::new());
+                }
+                None => superclass.find_method(&method_name),
+            },
+            Value::Class(_) => superclass.find_static_method(&method_name),
             _ => return Err(Error::undefined_var("Expected 'this' to be an instance", None)),
         };
 
-        let method_name = method.to_string();
-        if let Some(method) = superclass.find_method(&method_name) {
-            match method.bind(instance) {
+        if let Some(method) = found {
+            match method.bind(this) {
                 Some(t) => Ok(Value::Callable(t)),
                 None => Err(Error::invalid_context("Bind returned None", None)),
             }
@@ -339,22 +918,306 @@ impl Interpreter {
     }
 
     fn visit_comma_expr(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> ResultExec<Value> {
-        let _left = self.evaluate(&left)?;
+        let _left = self.evaluate(left)?;
 
-        self.evaluate(&right)
+        self.evaluate(right)
     }
 
-    fn visit_lambda_expr(&mut self, params: &Vec<Token>, body: &Vec<Stmt>) -> ResultExec<Value> {
+    fn visit_conditional_expr(
+        &mut self,
+        condition: &Box<Expr>,
+        then_branch: &Box<Expr>,
+        else_branch: &Box<Expr>,
+    ) -> ResultExec<Value> {
+        let condition = self.evaluate(condition)?;
+        if self.is_truthy(&condition) {
+            self.evaluate(then_branch)
+        } else {
+            self.evaluate(else_branch)
+        }
+    }
+
+    fn visit_match_expr(
+        &mut self,
+        keyword: &Token,
+        subject: &Box<Expr>,
+        arms: &Vec<MatchArm>,
+    ) -> ResultExec<Value> {
+        let value = self.evaluate(subject)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+                continue;
+            }
+
+            // each arm gets its own scope for the names bound by the pattern
+            let mut environment = Environment::from(&self.environment);
+            for (name, value) in bindings {
+                environment.define(&name.to_string(), value);
+            }
+            let previous = std::mem::replace(&mut self.environment, gc::alloc(environment));
+            let result = self.run_match_arm(arm);
+            self.environment = previous;
+
+            if let Some(value) = result? {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::no_match("No arm matches the value.", Some(keyword.clone())))
+    }
+
+    // None when the guard rejects the arm
+    fn run_match_arm(&mut self, arm: &MatchArm) -> ResultExec<Option<Value>> {
+        if let Some(guard) = &arm.guard {
+            let guard = self.evaluate(guard)?;
+            if !self.is_truthy(&guard) {
+                return Ok(None);
+            }
+        }
+
+        match arm.body.as_ref() {
+            Stmt::Expression { expression } => self.evaluate(expression).map(Some),
+            body => self.execute(body).map(|_| Some(Value::Null)),
+        }
+    }
+
+    // Tests `value` against `pattern`, collecting the values of the names it binds.
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(Token, Value)>,
+    ) -> ResultExec<bool> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Literal { value: literal, .. } => {
+                self.is_equal(value, &Value::from(literal.clone()), None)
+            }
+            Pattern::Binding { name } => {
+                bindings.push((name.clone(), value.clone()));
+                Ok(true)
+            }
+            Pattern::Object { fields, .. } => {
+                let Value::Instance(instance) = value else {
+                    return Ok(false);
+                };
+                for field in fields {
+                    match instance.borrow().field(&field.to_string()) {
+                        Some(value) => bindings.push((field.clone(), value)),
+                        None => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::List { elements, rest, .. } => {
+                let Value::List(list) = value else {
+                    return Ok(false);
+                };
+                let items = list.borrow().clone();
+                if items.len() < elements.len() || (rest.is_none() && items.len() > elements.len()) {
+                    return Ok(false);
+                }
+
+                for (element, item) in elements.iter().zip(items.iter()) {
+                    if !self.match_pattern(element, item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(rest) = rest.as_ref().filter(|rest| rest.to_string() != "_") {
+                    let remaining = items[elements.len()..].to_vec();
+                    bindings.push((rest.clone(), Value::list(remaining)));
+                }
+                Ok(true)
+            }
+            Pattern::Constant { value: expected } => {
+                let expected = self.evaluate(expected)?;
+                self.is_equal(value, &expected, None)
+            }
+            Pattern::Instance {
+                class,
+                paren,
+                fields,
+            } => {
+                let class = match self.evaluate(class)? {
+                    Value::Class(class) => class,
+                    Value::Constructor(constructor) => {
+                        return self.match_variant(&constructor, paren, fields, value, bindings);
+                    }
+                    _ => {
+                        return Err(Error::wrong_value_type(
+                            "Only classes and enum variants can be matched with '(...)'.",
+                            Some(paren.clone()),
+                        ));
+                    }
+                };
+                let names = class.init_params();
+                if fields.len() > names.len() {
+                    return Err(Error::wrong_arity(
+                        format!(
+                            "Class '{}' has {} fields to match but the pattern has {}.",
+                            class.name(),
+                            names.len(),
+                            fields.len()
+                        ),
+                        Some(paren.clone()),
+                    ));
+                }
+
+                let Value::Instance(instance) = value else {
+                    return Ok(false);
+                };
+                if !instance.borrow().is_instance_of(&class) {
+                    return Ok(false);
+                }
+
+                for (field, name) in fields.iter().zip(names.iter()) {
+                    // a class storing a parameter under another name can't be matched this way
+                    let Some(field_value) = instance.borrow().field(name) else {
+                        return Err(Error::undefined_var(
+                            format!(
+                                "Class '{}' has no field '{}' for the pattern, fields are matched by initializer parameter name.",
+                                class.name(),
+                                name
+                            ),
+                            Some(paren.clone()),
+                        ));
+                    };
+                    if !self.match_pattern(field, &field_value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    // `Shape.Rect(w, h)`: every field of the variant must be matched.
+    fn match_variant(
+        &mut self,
+        constructor: &Constructor,
+        paren: &Token,
+        fields: &[Pattern],
+        value: &Value,
+        bindings: &mut Vec<(Token, Value)>,
+    ) -> ResultExec<bool> {
+        if fields.len() != constructor.fields().len() {
+            return Err(Error::wrong_arity(
+                format!(
+                    "Variant '{}' has {} fields to match but the pattern has {}.",
+                    constructor,
+                    constructor.fields().len(),
+                    fields.len()
+                ),
+                Some(paren.clone()),
+            ));
+        }
+
+        let Value::EnumValue(enum_value) = value else {
+            return Ok(false);
+        };
+        if !constructor.built(enum_value) {
+            return Ok(false);
+        }
+
+        for (field, field_value) in fields.iter().zip(enum_value.values()) {
+            if !self.match_pattern(field, field_value, bindings)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Like `match_pattern`, but a value that doesn't fit the pattern is an error pointing at it.
+    fn destructure(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(Token, Value)>,
+    ) -> ResultExec<()> {
+        match pattern {
+            Pattern::List {
+                bracket,
+                elements,
+                rest,
+            } => {
+                let Value::List(list) = value else {
+                    return Err(Error::wrong_value_type(
+                        "Only lists can be destructured with '[...]'.",
+                        Some(bracket.clone()),
+                    ));
+                };
+                let items = list.borrow().clone();
+                let fits = match rest {
+                    Some(_) => items.len() >= elements.len(),
+                    None => items.len() == elements.len(),
+                };
+                if !fits {
+                    return Err(Error::wrong_value_type(
+                        format!(
+                            "Expected {}{} elements but the list has {}.",
+                            if rest.is_some() { "at least " } else { "" },
+                            elements.len(),
+                            items.len()
+                        ),
+                        Some(bracket.clone()),
+                    ));
+                }
+
+                for (element, item) in elements.iter().zip(items.iter()) {
+                    self.destructure(element, item, bindings)?;
+                }
+                if let Some(rest) = rest.as_ref().filter(|rest| rest.to_string() != "_") {
+                    let remaining = items[elements.len()..].to_vec();
+                    bindings.push((rest.clone(), Value::list(remaining)));
+                }
+                Ok(())
+            }
+            Pattern::Object { brace, fields } => {
+                let Value::Instance(instance) = value else {
+                    return Err(Error::wrong_value_type(
+                        "Only instances can be destructured with '{...}'.",
+                        Some(brace.clone()),
+                    ));
+                };
+                for field in fields {
+                    let value = instance.borrow().field(&field.to_string()).ok_or_else(|| {
+                        Error::undefined_var(
+                            format!("Undefined property '{}'.", field),
+                            Some(field.clone()),
+                        )
+                    })?;
+                    bindings.push((field.clone(), value));
+                }
+                Ok(())
+            }
+            _ => {
+                if self.match_pattern(pattern, value, bindings)? {
+                    return Ok(());
+                }
+                let location = match pattern {
+                    Pattern::Literal { token, .. } => Some(token.clone()),
+                    Pattern::Instance { paren, .. } => Some(paren.clone()),
+                    _ => None,
+                };
+                Err(Error::no_match("The value doesn't match the pattern.", location))
+            }
+        }
+    }
+
+    fn visit_lambda_expr(&mut self, params: &Vec<Param>, body: &Vec<Stmt>) -> ResultExec<Value> {
         let function = Function::Custom {
+            name: Rc::from("lambda"),
             params: Rc::new(params.to_vec()),
             body: Rc::new(body.to_vec()),
             closure: self.environment.clone(),
+            module: self.module(),
             is_initializer: false,
         };
         Ok(Value::Callable(function))
     }
 
-    fn is_truthy(&self, value: &Value) -> bool {
+    pub(crate) fn is_truthy(&self, value: &Value) -> bool {
         match value {
             Value::Null => false,
             Value::Bool(b) => *b,
@@ -362,14 +1225,41 @@ impl Interpreter {
         }
     }
 
-    fn is_equal(&self, left: &Value, right: &Value) -> bool {
+    // Between two instances, an `__eq__` or `equals` method of the left one decides, else one of
+    // the right one, called with the left. Other operands, nil included, never reach user code and
+    // compare like `Value`'s `PartialEq`, except NaN, which isn't equal to itself. `operator`
+    // locates errors calling the method.
+    fn is_equal(&mut self, left: &Value, right: &Value, operator: Option<&Token>) -> ResultExec<bool> {
         match (left, right) {
-            (Value::Null, Value::Null) => true,
-            (Value::Null, _) | (_, Value::Null) => false,
-            (Value::Bool(a), Value::Bool(b)) => a == b,
-            (Value::Number(a), Value::Number(b)) => a == b,
-            (Value::String(a), Value::String(b)) => a == b,
-            _ => false,
+            (Value::Instance(a), Value::Instance(b)) => {
+                let equals = |instance: &Rc<RefCell<Instance>>| {
+                    let instance = instance.borrow();
+                    instance.method("__eq__").or_else(|| instance.method("equals"))
+                };
+                let (equals, other) = match equals(a) {
+                    Some(equals) => (equals, right),
+                    None => match equals(b) {
+                        Some(equals) => (equals, left),
+                        None => return Ok(left == right),
+                    },
+                };
+                let result = equals.invoke(self, operator, std::slice::from_ref(other), &[])?;
+                Ok(self.is_truthy(&result))
+            }
+            (Value::Number(a), Value::Number(b)) => Ok(a == b),
+            // fields can be instances with their own `equals`
+            (Value::EnumValue(a), Value::EnumValue(b)) => {
+                if !a.same_variant(b) {
+                    return Ok(false);
+                }
+                for (a, b) in a.values().iter().zip(b.values()) {
+                    if !self.is_equal(a, b, operator)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(left == right),
         }
     }
 
@@ -390,18 +1280,37 @@ impl Interpreter {
         }
     }
 
+    fn check_integer_operands(&self, left: &Value, right: &Value) -> ResultExec<(i32, i32)> {
+        match (as_integer(left), as_integer(right)) {
+            (Some(l), Some(r)) => Ok((l, r)),
+            _ => Err(Error::wrong_value_type(
+                "Both operands must be integers.",
+                None,
+            )),
+        }
+    }
+
+    fn check_integer_operand(&self, operand: &Value) -> ResultExec<i32> {
+        as_integer(operand)
+            .ok_or_else(|| Error::wrong_value_type("Operand must be an integer.", None))
+    }
+
     fn look_up_var(&self, name: &Token) -> ResultExec<Value> {
-        let distance = self.locals.get(&name.to_string());
+        let distance = self.module.depth(name);
         if let Some(distance) = distance {
-            Environment::get_at(self.environment.clone(), *distance, &name.to_string())
+            Environment::get_at(self.environment.clone(), distance, &name.to_string())
         } else {
-            self.globals.borrow().get(&name.to_string())
+            self.module.globals().borrow().get(&name.to_string())
         }
     }
 
     // ----- Statement interpreting methods ----
 
     fn execute(&mut self, stmt: &Stmt) -> ResultExec<()> {
+        self.budget.step()?;
+        if gc::due() {
+            gc::collect();
+        }
         stmt.accept(self)
     }
 
@@ -413,13 +1322,15 @@ impl Interpreter {
     fn visit_function_stmt(
         &mut self,
         name: &Token,
-        params: &Vec<Token>,
+        params: &Vec<Param>,
         body: &Vec<Stmt>,
     ) -> ResultExec<()> {
         let function = Function::Custom {
+            name: Rc::from(name.to_string()),
             params: Rc::new(params.clone()),
             body: Rc::new(body.clone()),
             closure: Rc::clone(&self.environment),
+            module: self.module(),
             is_initializer: false,
         };
         self.environment
@@ -428,15 +1339,21 @@ impl Interpreter {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn visit_class_stmt(
         &mut self,
         name: &Token,
-        methods: &Vec<Box<Stmt>>,
         superclass: &Option<Box<Expr>>,
+        traits: &Vec<Expr>,
+        methods: &Vec<Box<Stmt>>,
+        getters: &Vec<Box<Stmt>>,
+        setters: &Vec<Box<Stmt>>,
+        static_methods: &Vec<Box<Stmt>>,
+        static_fields: &Vec<Stmt>,
     ) -> ResultExec<()> {
         let mut ev_superclass = None;
         if let Some(superclass) = superclass {
-            ev_superclass = Some(self.evaluate(&superclass)?);
+            ev_superclass = Some(self.evaluate(superclass)?);
             if !ev_superclass
                 .as_ref()
                 .is_some_and(|x| matches!(x, Value::Class(_)))
@@ -448,17 +1365,237 @@ impl Interpreter {
             }
         }
 
+        let mut ev_traits = Vec::new();
+        for t in traits {
+            match self.evaluate(t)? {
+                Value::Trait(t) => ev_traits.push(t),
+                _ => {
+                    return Err(Error::unexpected_expr(
+                        "Can only compose traits with 'with'.",
+                        Some(name.clone()),
+                    ))
+                }
+            }
+        }
+
         self.environment
             .borrow_mut()
             .define(&name.to_string(), Value::Null);
 
         if let Some(ref ev_superclass) = ev_superclass {
-            self.environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
+            self.environment = gc::alloc(Environment::from(&self.environment));
             self.environment
                 .borrow_mut()
                 .define("super", ev_superclass.clone());
         }
 
+        let klass = Rc::new(Class::new(
+            name.to_string(),
+            ev_superclass.clone(),
+            self.compose_traits(
+                name,
+                &ev_traits,
+                self.methods_map(methods, false)?,
+                &ev_superclass,
+            )?,
+            self.methods_map(getters, false)?,
+            self.methods_map(setters, false)?,
+            self.methods_map(static_methods, true)?,
+        ));
+
+        if ev_superclass.is_some() {
+            let enclosing = self
+                .environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("No enclosing environment to restore to.");
+
+            self.environment = enclosing;
+        }
+
+        self.environment
+            .borrow_mut()
+            .assign(&name.to_string(), Value::Class(Rc::clone(&klass)))?;
+
+        // initializers run once the class is bound, so they can refer to it
+        for field in static_fields {
+            if let Stmt::Var { name, initializer, .. } = field {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Null,
+                };
+                klass.set(name, &value);
+            } else {
+                return Err(Error::unexpected_stmt(
+                    "Should be a field declaration",
+                    None
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_trait_stmt(&mut self, name: &Token, methods: &Vec<Box<Stmt>>) -> ResultExec<()> {
+        let trait_ = Trait::new(name.to_string(), self.methods_map(methods, false)?);
+        self.environment
+            .borrow_mut()
+            .define(&name.to_string(), Value::Trait(Rc::new(trait_)));
+        Ok(())
+    }
+
+    fn visit_enum_stmt(&mut self, name: &Token, variants: &Vec<Variant>) -> ResultExec<()> {
+        let variants = variants
+            .iter()
+            .map(|v| {
+                let fields = v.fields.iter().map(|field| field.to_string()).collect();
+                EnumVariant::new(v.name.to_string(), fields)
+            })
+            .collect();
+        let enum_ = Enum::new(name.to_string(), variants);
+        self.environment
+            .borrow_mut()
+            .define(&name.to_string(), Value::Enum(Rc::new(enum_)));
+        Ok(())
+    }
+
+    fn visit_import_stmt(
+        &mut self,
+        path: &Token,
+        alias: &Option<Token>,
+        names: &Vec<Token>,
+    ) -> ResultExec<()> {
+        let module = self.load_module(path)?;
+
+        if let Some(alias) = alias {
+            self.environment
+                .borrow_mut()
+                .define(&alias.to_string(), Value::Module(module));
+            return Ok(());
+        }
+
+        for name in names {
+            let value = module.get(name)?;
+            self.environment.borrow_mut().define(&name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    // Runs the module at `path` the first time it is imported and returns the cached module
+    // afterwards. Paths are relative to the directory of the importing file.
+    fn load_module(&mut self, token: &Token) -> ResultExec<Rc<Module>> {
+        if !self.imports {
+            return Err(Error::permission_denied(
+                "import needs the 'fs_read' capability, which this script wasn't given.".to_string(),
+                Some(token.clone()),
+            ));
+        }
+
+        let relative = match self.module.path() {
+            Some(current) => current.with_file_name(token.to_string()),
+            None => PathBuf::from(token.to_string()),
+        };
+        let path = fs::canonicalize(&relative).map_err(|e| {
+            Error::import_error(
+                format!("Can't open '{}': {}.", relative.display(), e),
+                Some(token.clone()),
+            )
+        })?;
+
+        if let Some(start) = self.loading.iter().position(|p| *p == path) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(Error::import_error(
+                format!("Import cycle detected: {}.", cycle),
+                Some(token.clone()),
+            ));
+        }
+
+        if let Some(module) = self.modules.get(&path) {
+            return Ok(Rc::clone(module));
+        }
+
+        let import_error = |e: &dyn std::fmt::Display| {
+            Error::import_error(
+                format!("In '{}': {}", path.display(), e),
+                Some(token.clone()),
+            )
+        };
+        let source = fs::read_to_string(&path).map_err(|e| import_error(&e))?;
+        let tokens = Lexer::new(&source)
+            .scan_tokens()
+            .map_err(|e| import_error(&e))?;
+        let statements = parse_program(&tokens).map_err(|e| import_error(&e))?;
+
+        let module = Rc::new(Module::new(Some(path.clone()), &self.builtins));
+        Resolver::for_module(Rc::clone(&module))
+            .resolve_stmts(&statements)
+            .map_err(|e| import_error(&e))?;
+
+        self.loading.push(path.clone());
+        let previous_module = std::mem::replace(&mut self.module, Rc::clone(&module));
+        let previous_env = std::mem::replace(&mut self.environment, module.globals());
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+        self.environment = previous_env;
+        self.module = previous_module;
+        self.loading.pop();
+        result?;
+
+        self.modules.insert(path, Rc::clone(&module));
+        Ok(module)
+    }
+
+    // Trait methods are copied into the class, taking precedence over inherited ones. A method
+    // provided by more than one trait is an error unless the class overrides it.
+    fn compose_traits(
+        &self,
+        name: &Token,
+        traits: &[Rc<Trait>],
+        methods: HashMap<String, Function>,
+        superclass: &Option<Value>,
+    ) -> ResultExec<HashMap<String, Function>> {
+        let mut providers: HashMap<&str, &str> = HashMap::new();
+        let mut composed = HashMap::new();
+        for t in traits {
+            for (method_name, method) in t.methods() {
+                if methods.contains_key(method_name) {
+                    continue;
+                }
+
+                if let Some(other) = providers.insert(method_name, t.name()) {
+                    return Err(Error::invalid_context(
+                        format!(
+                            "Method '{}' is provided by both '{}' and '{}', class '{}' must override it.",
+                            method_name,
+                            other,
+                            t.name(),
+                            name
+                        ),
+                        Some(name.clone()),
+                    ));
+                }
+
+                let method = method
+                    .with_super(superclass.clone().unwrap_or(Value::Null))
+                    .ok_or_else(|| Error::invalid_context("Bind returned None", None))?;
+                composed.insert(method_name.clone(), method);
+            }
+        }
+
+        composed.extend(methods);
+        Ok(composed)
+    }
+
+    fn methods_map(
+        &self,
+        methods: &Vec<Box<Stmt>>,
+        is_static: bool,
+    ) -> ResultExec<HashMap<String, Function>> {
         let mut methods_map: HashMap<String, Function> = HashMap::new();
         for method in methods {
             if let Stmt::Function {
@@ -466,10 +1603,12 @@ impl Interpreter {
             } = method.as_ref()
             {
                 let function = Function::Custom {
+                    name: Rc::from(name.to_string()),
                     params: Rc::new(params.to_vec()),
                     body: Rc::new(body.to_vec()),
                     closure: self.environment.clone(),
-                    is_initializer: name.to_string() == "this",
+                    module: self.module(),
+                    is_initializer: !is_static && name.to_string() == "this",
                 };
                 methods_map.insert(name.to_string(), function);
             } else {
@@ -480,29 +1619,36 @@ impl Interpreter {
             }
         }
 
-        let klass = Class::new(name.to_string(), ev_superclass.clone(), methods_map);
-
-        if ev_superclass.is_some() {
-            let enclosing = self
-                .environment
-                .borrow()
-                .enclosing
-                .clone()
-                .expect("No enclosing environment to restore to.");
-
-            self.environment = enclosing;
-        }
-
-        self.environment
-            .borrow_mut()
-            .assign(&name.to_string(), Value::Class(Rc::new(klass)))?;
-        Ok(())
+        Ok(methods_map)
     }
 
     fn visit_print_stmt(&mut self, expr: &Expr) -> ResultExec<()> {
         let value = self.evaluate(expr)?;
-        println!("{}", value);
-        Ok(())
+        let text = self.stringify(&value)?;
+        writeln!(self.output, "{}", text)
+            .map_err(|e| Error::io_error(e.to_string(), None))
+    }
+
+    // How `print` shows a value: instances with a `__str__` method choose their text, in lists too
+    fn stringify(&mut self, value: &Value) -> ResultExec<String> {
+        match value {
+            Value::Instance(instance) => {
+                let method = instance.borrow().method("__str__");
+                match method {
+                    Some(method) => Ok(method.invoke(self, None, &[], &[])?.to_string()),
+                    None => Ok(value.to_string()),
+                }
+            }
+            Value::List(list) => {
+                let items = list.borrow().clone();
+                let mut parts = Vec::with_capacity(items.len());
+                for item in &items {
+                    parts.push(self.stringify(item)?);
+                }
+                Ok(format!("[{}]", parts.join(", ")))
+            }
+            _ => Ok(value.to_string()),
+        }
     }
 
     fn visit_return_stmt(&mut self, _keyword: &Token, value: &Option<Expr>) -> ResultExec<()> {
@@ -530,7 +1676,7 @@ impl Interpreter {
     fn visit_block_stmt(&mut self, stmts: &[Stmt]) -> ResultExec<()> {
         self.execute_block(
             stmts,
-            Rc::new(RefCell::new(Environment::from(&self.environment.clone()))),
+            gc::alloc(Environment::from(&self.environment.clone())),
         )
     }
 
@@ -542,9 +1688,9 @@ impl Interpreter {
     ) -> ResultExec<()> {
         let cond = self.evaluate(condition)?;
         if self.is_truthy(&cond) {
-            self.execute(&then_branch)?;
+            self.execute(then_branch)?;
         } else if let Some(else_branch) = else_branch {
-            self.execute(&else_branch)?;
+            self.execute(else_branch)?;
         }
 
         Ok(())
@@ -555,7 +1701,24 @@ impl Interpreter {
             let value = self.evaluate(condition)?;
             self.is_truthy(&value)
         } {
-            self.execute(&body)?;
+            match self.execute(body) {
+                Err(ControlFlow::Runtime(RuntimeControl::Break)) => break,
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for_in_stmt(&mut self, name: &Token, iterable: &Expr, body: &Stmt) -> ResultExec<()> {
+        let value = self.evaluate(iterable)?;
+        let mut iteration = Iteration::over(value, name)?;
+        while let Some(item) = iteration.next(self, name)? {
+            let mut environment = Environment::from(&self.environment);
+            environment.define(&name.to_string(), item);
+            match self.execute_block(std::slice::from_ref(body), gc::alloc(environment)) {
+                Err(ControlFlow::Runtime(RuntimeControl::Break)) => break,
+                result => result?,
+            }
         }
         Ok(())
     }
@@ -564,6 +1727,44 @@ impl Interpreter {
         Err(ControlFlow::Runtime(RuntimeControl::Break))
     }
 
+    // Evaluates a default parameter value in the environment of the call being set up.
+    pub(crate) fn evaluate_in(
+        &mut self,
+        expr: &Expr,
+        env: Rc<RefCell<Environment>>,
+        module: &Rc<Module>,
+    ) -> ResultExec<Value> {
+        let previous_module = std::mem::replace(&mut self.module, Rc::clone(module));
+        let previous_env = std::mem::replace(&mut self.environment, env);
+        let result = self.evaluate(expr);
+        self.environment = previous_env;
+        self.module = previous_module;
+        result
+    }
+
+    // Runs a single statement of a suspended generator body.
+    pub(crate) fn execute_in(
+        &mut self,
+        stmt: &Stmt,
+        env: Rc<RefCell<Environment>>,
+        module: &Rc<Module>,
+    ) -> ResultExec<()> {
+        self.execute_function(std::slice::from_ref(stmt), env, module)
+    }
+
+    // Runs a function body against the globals and resolved scopes of its declaring module.
+    pub fn execute_function(
+        &mut self,
+        stmts: &[Stmt],
+        env: Rc<RefCell<Environment>>,
+        module: &Rc<Module>,
+    ) -> ResultExec<()> {
+        let previous = std::mem::replace(&mut self.module, Rc::clone(module));
+        let result = self.execute_block(stmts, env);
+        self.module = previous;
+        result
+    }
+
     pub fn execute_block(
         &mut self,
         stmts: &[Stmt],
@@ -578,3 +1779,13 @@ impl Interpreter {
         result
     }
 }
+
+// Numbers with no fractional part that fit in an i32 are exact integers for bitwise operators.
+fn as_integer(value: &Value) -> Option<i32> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && *n >= i32::MIN as f32 && *n < i32::MAX as f32 => {
+            Some(*n as i32)
+        }
+        _ => None,
+    }
+}
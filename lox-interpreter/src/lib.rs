@@ -1,3 +1,5 @@
+#![allow(clippy::borrowed_box, clippy::ptr_arg, clippy::vec_box)]
+
 mod value;
 mod environment;
 mod errors;
@@ -5,7 +7,26 @@ mod interpreter;
 mod function;
 mod resolver;
 mod class;
+mod module;
+mod enums;
+mod generator;
+mod checker;
+mod engine;
+mod convert;
+mod foreign;
+mod output;
+mod limits;
+mod gc;
 
 pub use crate::interpreter::Interpreter;
 pub use crate::value::Value;
-pub use crate::resolver::Resolver;
\ No newline at end of file
+pub use crate::resolver::Resolver;
+pub use crate::checker::TypeChecker;
+pub use crate::engine::{Diagnostics, Engine, EngineBuilder};
+pub use crate::errors::{Error, ErrorKind};
+pub use crate::function::Arity;
+pub use crate::convert::{FromLox, IntoLox, IntoNative, NativeResult};
+pub use crate::foreign::ForeignClass;
+pub use crate::output::Capture;
+pub use crate::limits::Limits;
+pub use crate::gc::GcStats;
\ No newline at end of file
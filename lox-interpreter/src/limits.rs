@@ -0,0 +1,102 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use lox_syntax::Token;
+
+use crate::errors::{Error, ResultExec};
+
+// Bounds on a single run, so scripts from untrusted sources can't hang or crash the host. A run
+// is a call to `Interpreter::interpret` or a call from Rust with `Interpreter::call_value`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    // statements and expressions evaluated
+    pub steps: Option<u64>,
+    // nested calls, deep recursion overflows the Rust stack well before memory runs out. How
+    // deep a script can go depends on the stack of the thread running it and on the build.
+    pub depth: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+// the time is only read every so many steps, it is slower than counting
+const CLOCK_INTERVAL: u64 = 1024;
+
+// What the current run has used of its limits
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: Limits,
+    steps: u64,
+    depth: usize,
+    deadline: Option<Instant>,
+    // set by the host, from any thread, to stop the script; it stays set until the host clears it
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            steps: 0,
+            depth: 0,
+            deadline: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancelled)
+    }
+
+    // a new run starts, unless this is a call from a native inside the current one
+    pub fn start(&mut self) {
+        if self.depth == 0 {
+            self.steps = 0;
+            self.deadline = self.limits.time.map(|time| Instant::now() + time);
+        }
+    }
+
+    pub fn step(&mut self) -> ResultExec<()> {
+        self.steps += 1;
+        if let Some(max) = self.limits.steps
+            && self.steps > max
+        {
+            return Err(Error::interrupted(format!("Step limit of {} exceeded.", max), None));
+        }
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::interrupted("Cancelled by the host.", None));
+        }
+        if self.steps.is_multiple_of(CLOCK_INTERVAL)
+            && let (Some(deadline), Some(time)) = (self.deadline, self.limits.time)
+            && Instant::now() > deadline
+        {
+            return Err(Error::interrupted(format!("Time limit of {:?} exceeded.", time), None));
+        }
+        Ok(())
+    }
+
+    // `paren` is the call, to locate the error
+    pub fn enter_call(&mut self, paren: Option<&Token>) -> ResultExec<()> {
+        if let Some(max) = self.limits.depth
+            && self.depth >= max
+        {
+            return Err(Error::stack_overflow(
+                format!("Maximum call depth of {} exceeded.", max),
+                paren.cloned(),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn exit_call(&mut self) {
+        self.depth -= 1;
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use lox_syntax::Token;
+
+use crate::{
+    environment::Environment,
+    errors::{Error, ResultExec},
+    gc,
+    Value,
+};
+
+// A single source file: its top-level bindings, the scope distances the resolver computed for
+// its code and the names it exports. Functions keep a reference to the module they were declared
+// in, so they see its globals wherever they are called from.
+#[derive(Debug)]
+pub struct Module {
+    path: RefCell<Option<PathBuf>>,
+    globals: Rc<RefCell<Environment>>,
+    // scope distances of local variables, by token id
+    locals: RefCell<HashMap<usize, usize>>,
+    exports: RefCell<HashSet<String>>,
+}
+
+impl Module {
+    pub fn new(path: Option<PathBuf>, builtins: &Rc<RefCell<Environment>>) -> Self {
+        Self {
+            path: RefCell::new(path),
+            globals: gc::alloc(Environment::from(builtins)),
+            locals: RefCell::new(HashMap::new()),
+            exports: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.borrow().clone()
+    }
+
+    pub fn set_path(&self, path: PathBuf) {
+        *self.path.borrow_mut() = Some(path);
+    }
+
+    pub fn globals(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.globals)
+    }
+
+    pub fn resolve(&self, name: &Token, depth: usize) {
+        self.locals.borrow_mut().insert(name.id, depth);
+    }
+
+    pub fn depth(&self, name: &Token) -> Option<usize> {
+        self.locals.borrow().get(&name.id).copied()
+    }
+
+    pub fn export(&self, name: &Token) {
+        self.exports.borrow_mut().insert(name.to_string());
+    }
+
+    pub fn get(&self, name: &Token) -> ResultExec<Value> {
+        let key = name.to_string();
+        if !self.exports.borrow().contains(&key) {
+            return Err(Error::import_error(
+                format!("Module '{}' does not export '{}'.", self, key),
+                Some(name.clone()),
+            ));
+        }
+
+        self.globals.borrow().get(&key)
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.borrow().as_ref().and_then(|p| p.file_name()) {
+            Some(name) => write!(f, "{}", name.to_string_lossy()),
+            None => write!(f, "<script>"),
+        }
+    }
+}
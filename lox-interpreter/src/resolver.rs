@@ -1,9 +1,12 @@
 use std::{cell::RefCell, collections::HashMap, ops::Deref, rc::Rc};
 
-use lox_syntax::{Expr, ExprVisitor, Node, Stmt, StmtVisitor, Token};
+use lox_syntax::{
+    Expr, ExprVisitor, MatchArm, Node, Param, Pattern, Stmt, StmtVisitor, Token, VarKind,
+};
 
 use crate::{
     errors::{ControlFlow, Error, ResultExec},
+    module::Module,
     Interpreter,
 };
 
@@ -13,6 +16,10 @@ enum FunctionType {
     Function,
     Initializer,
     Method,
+    Getter,
+    Setter,
+    // a function or method with a `yield` in its body
+    Generator,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -20,11 +27,17 @@ enum ClassType {
     None,
     Class,
     Subclass,
+    Trait,
 }
 
 pub struct Resolver {
-    interpreter: Rc<RefCell<Interpreter>>,
+    module: Rc<Module>,
     scopes: Vec<HashMap<String, (bool, bool)>>, // (is_defined, is_used)
+    // how each name was declared, for every scope and for the top level
+    declarations: Vec<HashMap<String, (VarKind, Token)>>,
+    globals: HashMap<String, (VarKind, Token)>,
+    // variant names of the enums declared in this module, by the id of the enum name's token
+    enums: HashMap<usize, Vec<String>>,
     current_function: FunctionType,
     current_class: ClassType,
 }
@@ -34,10 +47,21 @@ impl ExprVisitor<ResultExec<()>> for Resolver {
         match expr {
             Expr::Variable { name } => self.visit_var_expr(name),
             Expr::Assign { name, value } => self.visit_assign_expr(name, value),
+            Expr::AssignPattern { pattern, value } => {
+                self.resolve(&Node::Expr(value.clone()))?;
+                for name in pattern.bindings() {
+                    self.check_assignable(name)?;
+                    self.resolve_local(name);
+                }
+                Ok(())
+            }
             Expr::Binary { left, right, .. } => self.visit_binary_expr(left, right),
             Expr::Call {
-                callee, arguments, ..
-            } => self.visit_call_expr(callee, arguments),
+                callee,
+                arguments,
+                named_arguments,
+                ..
+            } => self.visit_call_expr(callee, arguments, named_arguments),
             Expr::Grouping { expression } => self.visit_grouping_expr(expression),
             Expr::Literal { .. } => Ok(()),
             Expr::Logical { left, right, .. } => {
@@ -45,12 +69,44 @@ impl ExprVisitor<ResultExec<()>> for Resolver {
                 self.resolve(&Node::Expr(right.clone()))?;
                 Ok(())
             }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve(&Node::Expr(condition.clone()))?;
+                self.resolve(&Node::Expr(then_branch.clone()))?;
+                self.resolve(&Node::Expr(else_branch.clone()))?;
+                Ok(())
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.resolve(&Node::Expr(Box::new(element.clone())))?;
+                }
+                Ok(())
+            }
+            Expr::Match { subject, arms, .. } => self.visit_match_expr(subject, arms),
+            Expr::Lambda { params, body } => self.resolve_function(params, body, FunctionType::Function),
+            Expr::MethodRef { object, .. } => self.resolve(&Node::Expr(object.clone())),
+            Expr::Index { object, index, .. } => {
+                self.resolve(&Node::Expr(object.clone()))?;
+                self.resolve(&Node::Expr(index.clone()))
+            }
+            Expr::Postfix { target, .. } => {
+                if let Expr::Variable { name } = target.as_ref() {
+                    self.check_assignable(name)?;
+                }
+                self.resolve(&Node::Expr(target.clone()))
+            }
             Expr::Unary { right, .. } => {
                 self.resolve(&Node::Expr(right.clone()))?;
                 Ok(())
             }
-            Expr::Get { object, .. } => {
+            Expr::Get { object, name, .. } => {
                 self.resolve(&Node::Expr(object.clone()))?;
+                if let Expr::Variable { name: enum_name } = object.as_ref() {
+                    self.check_variant(enum_name, name)?;
+                }
                 Ok(())
             }
             Expr::Set { object, value, .. } => {
@@ -64,7 +120,9 @@ impl ExprVisitor<ResultExec<()>> for Resolver {
                         "Can't use 'super' outside of a class.", 
                         Some(keyword.clone())
                     ));
-                } else if self.current_class != ClassType::Subclass {
+                } else if self.current_class != ClassType::Subclass
+                    && self.current_class != ClassType::Trait
+                {
                     return Err(Error::invalid_context(
                         "Can't use 'super' in a class with no superclass.", 
                         Some(keyword.clone())
@@ -89,8 +147,28 @@ impl StmtVisitor<ResultExec<()>> for Resolver {
     fn visit_stmt(&mut self, stmt: &Stmt) -> ResultExec<()> {
         match stmt {
             Stmt::Block { statements } => self.visit_block_stmt(statements),
-            Stmt::Var { name, initializer } => self.visit_var_stmt(name, initializer),
-            Stmt::Function { name, params, body } => self.visit_function_stmt(name, params, body),
+            Stmt::Var {
+                name,
+                initializer,
+                kind,
+                ..
+            } => self.visit_var_stmt(name, initializer, *kind),
+            Stmt::VarPattern {
+                pattern,
+                initializer,
+                kind,
+            } => {
+                self.resolve(&Node::Expr(Box::new(initializer.clone())))?;
+                self.resolve_pattern_classes(pattern)?;
+                for name in pattern.bindings() {
+                    self.declare_as(name, *kind)?;
+                    self.define(name);
+                }
+                Ok(())
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => self.visit_function_stmt(name, params, body),
             Stmt::Expression { expression } => self.visit_expression_stmt(expression),
             Stmt::If {
                 condition,
@@ -98,19 +176,84 @@ impl StmtVisitor<ResultExec<()>> for Resolver {
                 else_branch,
             } => self.visit_if_stmt(condition, then_branch, else_branch),
             Stmt::Print { expression } => self.visit_print_stmt(expression),
-            Stmt::Return { value, .. } => self.visit_return_stmt(value),
+            Stmt::Return { keyword, value } => self.visit_return_stmt(keyword, value),
             Stmt::While { condition, body } => self.visit_while_stmt(condition, body),
-            Stmt::Class { name, methods, superclass} => self.visit_class_stmt(name, methods, superclass),
-            _ => Err(Error::unexpected_stmt("unknown statement type", None)),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                self.resolve(&Node::Expr(Box::new(iterable.clone())))?;
+                self.begin_scope();
+                self.declare(name)?;
+                self.define(name);
+                self.resolve(&Node::Stmt(body.clone()))?;
+                self.end_scope()
+            }
+            // the parser only accepts `break` inside a loop
+            Stmt::Break => Ok(()),
+            Stmt::Yield { keyword, value } => self.visit_yield_stmt(keyword, value),
+            Stmt::Trait { name, methods } => self.visit_trait_stmt(name, methods),
+            Stmt::Enum { name, variants } => {
+                // an enum can't be reassigned, so its variants are known wherever the name is
+                self.declare_as(name, VarKind::Const)?;
+                self.define(name);
+                let variants = variants.iter().map(|v| v.name.to_string()).collect();
+                self.enums.insert(name.id, variants);
+                Ok(())
+            }
+            Stmt::Import { alias, names, .. } => {
+                for name in alias.iter().chain(names) {
+                    self.declare(name)?;
+                    self.define(name);
+                }
+                Ok(())
+            }
+            Stmt::Export { name, declaration } => {
+                if !self.scopes.is_empty() {
+                    return Err(Error::invalid_context(
+                        "Can only export top-level declarations.",
+                        Some(name.clone()),
+                    ));
+                }
+                self.resolve(&Node::Stmt(declaration.clone()))
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                traits,
+                methods,
+                getters,
+                setters,
+                static_methods,
+                static_fields,
+                ..
+            } => self.visit_class_stmt(
+                name,
+                superclass,
+                traits,
+                methods,
+                getters,
+                setters,
+                static_methods,
+                static_fields,
+            ),
         }
     }
 }
 
 impl Resolver {
     pub fn new(interpreter: Rc<RefCell<Interpreter>>) -> Self {
+        Self::for_module(interpreter.borrow().module())
+    }
+
+    pub(crate) fn for_module(module: Rc<Module>) -> Self {
         Self {
-            interpreter,
+            module,
             scopes: Vec::new(),
+            declarations: Vec::new(),
+            globals: HashMap::new(),
+            enums: HashMap::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
         }
@@ -141,13 +284,28 @@ impl Resolver {
         Ok(())
     }
 
-    fn visit_class_stmt(&mut self, c_name: &Token, methods: &Vec<Box<Stmt>>, superclass: &Option<Box<Expr>>) -> ResultExec<()> {
+    #[allow(clippy::too_many_arguments)]
+    fn visit_class_stmt(
+        &mut self,
+        c_name: &Token,
+        superclass: &Option<Box<Expr>>,
+        traits: &Vec<Expr>,
+        methods: &Vec<Box<Stmt>>,
+        getters: &Vec<Box<Stmt>>,
+        setters: &Vec<Box<Stmt>>,
+        static_methods: &Vec<Box<Stmt>>,
+        static_fields: &Vec<Stmt>,
+    ) -> ResultExec<()> {
         let enclosing_class = self.current_class;
         self.current_class = ClassType::Class;
 
-        self.declare(c_name);
+        self.declare(c_name)?;
         self.define(c_name);
 
+        for t in traits {
+            self.resolve(&Node::Expr(Box::new(t.clone())))?;
+        }
+
         if let Some(superclass) = superclass {
             match superclass.deref() {
                 Expr::Variable { name } => {
@@ -183,7 +341,7 @@ impl Resolver {
             .insert("this".to_string(), (true, false));
 
         for method in methods {
-            if let Stmt::Function { params, body, name } = method.as_ref() {
+            if let Stmt::Function { params, body, name, .. } = method.as_ref() {
                 let declaration = if name.to_string() == "init" {
                     FunctionType::Initializer
                 } else {
@@ -198,6 +356,30 @@ impl Resolver {
             }
         }
 
+        // accessors, then static methods which get `this` bound to the class itself
+        let accessors = getters
+            .iter()
+            .map(|getter| (getter, FunctionType::Getter))
+            .chain(setters.iter().map(|setter| (setter, FunctionType::Setter)))
+            .chain(static_methods.iter().map(|method| (method, FunctionType::Method)));
+        for (method, declaration) in accessors {
+            if let Stmt::Function { name, params, body, .. } = method.as_ref() {
+                let getter = declaration == FunctionType::Getter;
+                self.resolve_function(params, body, declaration)?;
+                if getter && !body.iter().any(Stmt::always_returns) {
+                    return Err(Error::unexpected_stmt(
+                        "A getter must return a value.",
+                        Some(name.clone()),
+                    ));
+                }
+            } else {
+                return Err(Error::unexpected_stmt(
+                    "Should be a function",
+                    None
+                ));
+            }
+        }
+
         self.end_scope()?;
         if superclass.is_some() {
             self.end_scope()?;
@@ -205,11 +387,61 @@ impl Resolver {
 
         self.current_class = enclosing_class;
 
+        // field initializers are evaluated outside of any method, where `this` is not bound
+        for field in static_fields {
+            if let Stmt::Var { initializer: Some(init), .. } = field {
+                self.resolve(&Node::Expr(Box::new(init.clone())))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_trait_stmt(&mut self, t_name: &Token, methods: &Vec<Box<Stmt>>) -> ResultExec<()> {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Trait;
+
+        self.declare(t_name)?;
+        self.define(t_name);
+
+        // trait methods are closed over the composing class's `super` before `this` is bound
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .expect("Scope must exist")
+            .insert("super".to_string(), (true, false));
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .expect("Scope must exist")
+            .insert("this".to_string(), (true, false));
+
+        for method in methods {
+            if let Stmt::Function { params, body, .. } = method.as_ref() {
+                self.resolve_function(params, body, FunctionType::Method)?;
+            } else {
+                return Err(Error::unexpected_stmt(
+                    "Should be a function",
+                    None
+                ));
+            }
+        }
+
+        self.end_scope()?;
+        self.end_scope()?;
+
+        self.current_class = enclosing_class;
+
         Ok(())
     }
 
-    fn visit_var_stmt(&mut self, name: &Token, initializer: &Option<Expr>) -> ResultExec<()> {
-        self.declare(name);
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        kind: VarKind,
+    ) -> ResultExec<()> {
+        self.declare_as(name, kind)?;
         if let Some(init) = initializer {
             self.resolve(&Node::Expr(Box::new(init.clone())))?;
         }
@@ -220,10 +452,10 @@ impl Resolver {
     fn visit_function_stmt(
         &mut self,
         name: &Token,
-        parameters: &[Token],
+        parameters: &[Param],
         body: &[Stmt],
     ) -> ResultExec<()> {
-        self.declare(name);
+        self.declare(name)?;
         self.define(name);
 
         self.resolve_function(parameters, body, FunctionType::Function)?;
@@ -254,15 +486,19 @@ impl Resolver {
         Ok(())
     }
 
-    fn visit_return_stmt(&mut self, value: &Option<Expr>) -> ResultExec<()> {
-        match self.current_function {
-            FunctionType::None => {
-                return Err(Error::unexpected_stmt(
-                    "return statement outside of function",
-                    None,
-                ))
-            }
-            _ => {}
+    fn visit_return_stmt(&mut self, keyword: &Token, value: &Option<Expr>) -> ResultExec<()> {
+        if self.current_function == FunctionType::None {
+            return Err(Error::unexpected_stmt(
+                "return statement outside of function",
+                None,
+            ))
+        }
+
+        if value.is_none() && self.current_function == FunctionType::Getter {
+            return Err(Error::unexpected_stmt(
+                "A getter must return a value.",
+                Some(keyword.clone()),
+            ));
         }
 
         if let Some(value) = value {
@@ -272,12 +508,48 @@ impl Resolver {
                     None,
                 ));
             }
+            if self.current_function == FunctionType::Setter {
+                return Err(Error::unexpected_stmt(
+                    "Can't return a value from a setter.",
+                    Some(keyword.clone()),
+                ));
+            }
+            if self.current_function == FunctionType::Generator {
+                return Err(Error::unexpected_stmt(
+                    "Can't return a value from a generator.",
+                    Some(keyword.clone()),
+                ));
+            }
             self.resolve(&Node::Expr(Box::new(value.clone())))?;
         }
 
         Ok(())
     }
 
+    fn visit_yield_stmt(&mut self, keyword: &Token, value: &Option<Expr>) -> ResultExec<()> {
+        match self.current_function {
+            FunctionType::None => {
+                return Err(Error::invalid_context(
+                    "Can't yield outside of a function.",
+                    Some(keyword.clone()),
+                ));
+            }
+            // only yields in the statements of the body make a generator
+            FunctionType::Generator => {}
+            _ => {
+                return Err(Error::invalid_context(
+                    "Can't yield from inside an expression.",
+                    Some(keyword.clone()),
+                ));
+            }
+        }
+
+        if let Some(value) = value {
+            self.resolve(&Node::Expr(Box::new(value.clone())))?;
+        }
+        Ok(())
+    }
+
     fn visit_while_stmt(&mut self, condition: &Expr, body: &Box<Stmt>) -> ResultExec<()> {
         self.resolve(&Node::Expr(Box::new(condition.clone())))?;
         self.resolve(&Node::Stmt(body.clone()))?;
@@ -315,24 +587,107 @@ impl Resolver {
 
     fn visit_assign_expr(&mut self, name: &Token, value: &Box<Expr>) -> ResultExec<()> {
         self.resolve(&Node::Expr(value.clone()))?;
+        self.check_assignable(name)?;
         self.resolve_local(name);
         Ok(())
     }
 
+    // the innermost declaration `name` refers to
+    fn declaration_of(&self, name: &Token) -> Option<&(VarKind, Token)> {
+        let key = name.to_string();
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&key))
+            .map_or(self.globals.get(&key), |i| self.declarations[i].get(&key))
+    }
+
+    // Rejects assigning to the closest declaration of `name` when it is a `const`.
+    fn check_assignable(&self, name: &Token) -> ResultExec<()> {
+        let key = name.to_string();
+        match self.declaration_of(name) {
+            // token lines count from 0
+            Some((VarKind::Const, declared)) => Err(Error::invalid_assignment(
+                format!("Can't assign to constant '{}' declared on line {}.", key, declared.line + 1),
+                Some(name.clone()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // `Shape.Circel` is reported before the program runs when `Shape` is an enum of this module
+    fn check_variant(&self, enum_name: &Token, variant: &Token) -> ResultExec<()> {
+        let Some((_, declared)) = self.declaration_of(enum_name) else {
+            return Ok(());
+        };
+        match self.enums.get(&declared.id) {
+            Some(variants) if !variants.contains(&variant.to_string()) => {
+                Err(Error::undefined_var(
+                    format!("Enum '{}' has no variant '{}'.", enum_name, variant),
+                    Some(variant.clone()),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn visit_binary_expr(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> ResultExec<()> {
         self.resolve(&Node::Expr(left.clone()))?;
         self.resolve(&Node::Expr(right.clone()))?;
         Ok(())
     }
 
-    fn visit_call_expr(&mut self, callee: &Box<Expr>, arguments: &[Expr]) -> ResultExec<()> {
+    fn visit_call_expr(
+        &mut self,
+        callee: &Box<Expr>,
+        arguments: &[Expr],
+        named_arguments: &[(Token, Expr)],
+    ) -> ResultExec<()> {
         self.resolve(&Node::Expr(callee.clone()))?;
-        for argument in arguments {
+        for argument in arguments.iter().chain(named_arguments.iter().map(|(_, a)| a)) {
             self.resolve(&Node::Expr(Box::new(argument.clone())))?;
         }
         Ok(())
     }
 
+    fn visit_match_expr(&mut self, subject: &Box<Expr>, arms: &[MatchArm]) -> ResultExec<()> {
+        self.resolve(&Node::Expr(subject.clone()))?;
+        for arm in arms {
+            // classes in the pattern are looked up where the match is
+            self.resolve_pattern_classes(&arm.pattern)?;
+
+            self.begin_scope();
+            for name in arm.pattern.bindings() {
+                self.declare(name)?;
+                self.define(name);
+            }
+            if let Some(guard) = &arm.guard {
+                self.resolve(&Node::Expr(Box::new(guard.clone())))?;
+            }
+            self.resolve(&Node::Stmt(arm.body.clone()))?;
+            self.end_scope()?;
+        }
+        Ok(())
+    }
+
+    fn resolve_pattern_classes(&mut self, pattern: &Pattern) -> ResultExec<()> {
+        match pattern {
+            Pattern::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_pattern_classes(element)?;
+                }
+            }
+            Pattern::Instance { class, fields, .. } => {
+                self.resolve(&Node::Expr(class.clone()))?;
+                for field in fields {
+                    self.resolve_pattern_classes(field)?;
+                }
+            }
+            Pattern::Constant { value } => self.resolve(&Node::Expr(value.clone()))?,
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn visit_grouping_expr(&mut self, expression: &Box<Expr>) -> ResultExec<()> {
         self.resolve(&Node::Expr(expression.clone()))?;
         Ok(())
@@ -342,9 +697,11 @@ impl Resolver {
 
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.declarations.push(HashMap::new());
     }
 
     fn end_scope(&mut self) -> ResultExec<()> {
+        self.declarations.pop();
         if let Some(scope) = self.scopes.pop() {
             for (name, (defined, used)) in scope {
                 if defined && !used  && name != "this" && name != "super" {
@@ -356,13 +713,31 @@ impl Resolver {
         Ok(())
     }
 
-    fn declare(&mut self, name: &Token) {
-        if self.scopes.is_empty() {
-            return;
+    fn declare(&mut self, name: &Token) -> ResultExec<()> {
+        self.declare_as(name, VarKind::Var)
+    }
+
+    // Only `var` declarations can share a scope with another declaration of the same name.
+    fn declare_as(&mut self, name: &Token, kind: VarKind) -> ResultExec<()> {
+        let key = name.to_string();
+        let declarations = match self.declarations.last_mut() {
+            Some(declarations) => declarations,
+            None => &mut self.globals,
+        };
+        if let Some((previous, declaration)) = declarations.get(&key)
+            && (*previous != VarKind::Var || kind != VarKind::Var)
+        {
+            return Err(Error::invalid_context(
+                format!("'{}' is already declared in this scope on line {}.", key, declaration.line),
+                Some(name.clone()),
+            ));
         }
+        declarations.insert(key, (kind, name.clone()));
 
-        let scope = self.scopes.last_mut().unwrap();
-        scope.insert(name.to_string(), (false, false));
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (false, false));
+        }
+        Ok(())
     }
 
     fn define(&mut self, name: &Token) {
@@ -386,23 +761,35 @@ impl Resolver {
             .find(|(_, scope)| scope.contains_key(&name.to_string()))
             .map(|(i, _)| i)
         {
-            println!("here 2");
-            self.interpreter.borrow_mut().resolve(name, distance);
+            self.module.resolve(name, distance);
         }
     }
 
     fn resolve_function(
         &mut self,
-        parameters: &[Token],
+        parameters: &[Param],
         body: &[Stmt],
         function_type: FunctionType,
     ) -> ResultExec<()> {
         let enclosing_function = self.current_function.clone();
         self.current_function = function_type;
+        if body.iter().any(Stmt::yields) {
+            if !matches!(self.current_function, FunctionType::Function | FunctionType::Method) {
+                return Err(Error::invalid_context(
+                    "Only functions and methods can yield.",
+                    None,
+                ));
+            }
+            self.current_function = FunctionType::Generator;
+        }
         self.begin_scope();
         for param in parameters {
-            self.declare(param);
-            self.define(param);
+            // defaults can refer to the parameters before them
+            if let Some(default) = &param.default {
+                self.resolve(&Node::Expr(Box::new(default.clone())))?;
+            }
+            self.declare(&param.name)?;
+            self.define(&param.name);
         }
 
         for stmt in body {
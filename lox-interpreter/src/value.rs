@@ -1,8 +1,21 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
+};
 
 use lox_syntax::Literal;
 
-use crate::{class::{Class, Instance}, function::Function};
+use crate::{
+    class::{Class, Instance, Trait},
+    enums::{Constructor, Enum, EnumValue},
+    function::Function,
+    gc::{self, Trace, Tracer},
+    generator::Generator,
+    module::Module,
+};
 
 #[derive(Clone, Debug)]
 pub enum Value {
@@ -12,7 +25,97 @@ pub enum Value {
     Null,
     Callable(Function),
     Class(Rc<Class>),
+    Trait(Rc<Trait>),
     Instance(Rc<RefCell<Instance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Module(Rc<Module>),
+    Enum(Rc<Enum>),
+    Constructor(Constructor),
+    EnumValue(Rc<EnumValue>),
+    Generator(Rc<RefCell<Generator>>),
+}
+
+impl Value {
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(gc::alloc(items))
+    }
+}
+
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::Callable(function) => function.trace(tracer),
+            Value::Class(class) => tracer.shared(class),
+            Value::Trait(t) => tracer.shared(t),
+            Value::Instance(instance) => tracer.edge(instance),
+            Value::List(list) => tracer.edge(list),
+            Value::EnumValue(value) => tracer.shared(value),
+            Value::Generator(generator) => tracer.shared(generator),
+            // a module keeps its globals, which are roots anyway
+            _ => {}
+        }
+    }
+}
+
+// Numbers, strings, booleans and nil are equal by value, enum values by variant and fields, and
+// everything else is only equal to itself. It is `==` without the `equals` methods of instances.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Callable(a), Value::Callable(b)) => a == b,
+            (Value::Class(a), Value::Class(b)) => a == b,
+            (Value::Trait(a), Value::Trait(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => {
+                Rc::ptr_eq(a, b)
+                    || matches!((a.try_borrow(), b.try_borrow()), (Ok(a), Ok(b)) if *a == *b)
+            }
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            (Value::Enum(a), Value::Enum(b)) => Rc::ptr_eq(a, b),
+            (Value::Constructor(a), Value::Constructor(b)) => a == b,
+            (Value::EnumValue(a), Value::EnumValue(b)) => a == b,
+            (Value::Generator(a), Value::Generator(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// NaN is equal to itself here, unlike with `==` in scripts, so it can be found again as a key
+impl Eq for Value {}
+
+// Reference types hash their identity, never what's behind their `RefCell`s, so a value keeps its
+// hash as scripts change it.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            // 0 and -0 are equal, and so are all NaNs
+            Value::Number(n) if *n == 0.0 => 0.0f32.to_bits().hash(state),
+            Value::Number(n) if n.is_nan() => f32::NAN.to_bits().hash(state),
+            Value::Number(n) => n.to_bits().hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Bool(b) => b.hash(state),
+            Value::Null => {}
+            Value::Callable(function) => function.hash(state),
+            Value::Class(class) => class.hash(state),
+            Value::Trait(t) => Rc::as_ptr(t).hash(state),
+            // an instance borrowed mutably is only equal to itself
+            Value::Instance(instance) => match instance.try_borrow() {
+                Ok(instance) => instance.hash(state),
+                Err(_) => Rc::as_ptr(instance).hash(state),
+            },
+            Value::List(list) => Rc::as_ptr(list).hash(state),
+            Value::Module(module) => Rc::as_ptr(module).hash(state),
+            Value::Enum(e) => Rc::as_ptr(e).hash(state),
+            Value::Constructor(constructor) => constructor.hash(state),
+            Value::EnumValue(value) => value.hash(state),
+            Value::Generator(generator) => Rc::as_ptr(generator).hash(state),
+        }
+    }
 }
 
 impl From<Literal> for Value {
@@ -33,9 +136,25 @@ impl Display for Value {
             Value::Number(x) => write!(f, "{}", x),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Null => write!(f, "null"),
+            Value::Callable(function) => write!(f, "{}", function),
             Value::Class(c) => write!(f, "{}", c),
+            Value::Trait(t) => write!(f, "{}", t),
             Value::Instance(i) => write!(f, "{}", i.borrow()),
-            _ => Err(std::fmt::Error),
+            Value::List(l) => {
+                write!(f, "[")?;
+                for (i, value) in l.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Module(m) => write!(f, "<module {}>", m),
+            Value::Enum(e) => write!(f, "{}", e),
+            Value::Constructor(c) => write!(f, "{}", c),
+            Value::EnumValue(v) => write!(f, "{}", v),
+            Value::Generator(_) => write!(f, "<generator>"),
         }
     }
 }
@@ -0,0 +1,46 @@
+use lox_interpreter::{Error, ErrorKind};
+
+// What the natives of the standard library may do on the host. Natives needing a capability
+// the script wasn't given are still defined, and fail with a permission error when called.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    pub fs_read: bool,
+    pub fs_write: bool,
+    pub env: bool,
+    pub clock: bool,
+    pub exit: bool,
+    pub random: bool,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self {
+            fs_read: true,
+            fs_write: true,
+            env: true,
+            clock: true,
+            exit: true,
+            random: true,
+        }
+    }
+
+    // nothing reaching outside of the interpreter, for untrusted scripts
+    pub fn locked_down() -> Self {
+        Self::default()
+    }
+}
+
+// the error of a native called without its capability
+pub(crate) fn require(granted: bool, native: &str, capability: &str) -> Result<(), Error> {
+    if granted {
+        return Ok(());
+    }
+
+    Err(Error::new(
+        ErrorKind::PermissionDenied(format!(
+            "{}() needs the '{}' capability, which this script wasn't given.",
+            native, capability
+        )),
+        None,
+    ))
+}
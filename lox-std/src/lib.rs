@@ -1,16 +1,178 @@
-use std::{cell::RefCell, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
-
-use lox_interpreter::{Interpreter, Value};
-
-// standard library injection
-// for now there is only clock()
-pub fn set_stdlib(interpreter: Rc<RefCell<Interpreter>>) {
-    interpreter.borrow_mut().set_global_fn("clock", 0, |_args| {
-        Value::Number(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs_f32(),
-        )
-    });
-}
\ No newline at end of file
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    env, fs,
+    hash::BuildHasher,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lox_interpreter::{Arity, Error, ErrorKind, Interpreter, Value};
+
+mod capabilities;
+
+pub use capabilities::Capabilities;
+use capabilities::require;
+
+// standard library injection, with every capability
+pub fn set_stdlib(interpreter: &mut Interpreter) {
+    set_stdlib_with(interpreter, Capabilities::all());
+}
+
+// the standard library, with natives reaching the host limited to `capabilities`
+pub fn set_stdlib_with(interpreter: &mut Interpreter, capabilities: Capabilities) {
+    // modules are read from files too
+    interpreter.set_imports(capabilities.fs_read);
+
+    interpreter.set_typed_fn("clock", move || -> Result<f64, Error> {
+        require(capabilities.clock, "clock", "clock")?;
+        Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs_f64())
+    });
+
+    // input() is the next line of the input, nil at its end
+    interpreter.set_global_fn("input", Arity::exact(0), |interpreter, _| {
+        match interpreter.read_line() {
+            Ok(Some(line)) => Ok(Value::String(line)),
+            Ok(None) => Ok(Value::Null),
+            Err(e) => Err(io_error(e)),
+        }
+    });
+
+    // map(list, fn) is a new list with fn called on every element
+    interpreter.set_global_fn("map", Arity::exact(2), |interpreter, args| {
+        let Value::List(list) = &args[0] else {
+            return Err(Error::new(
+                ErrorKind::WrongValueType("The first argument of map() must be a list.".to_string()),
+                None,
+            ));
+        };
+
+        // the callback may change the list, so it iterates over a copy
+        let items = list.borrow().clone();
+        let mut mapped = Vec::with_capacity(items.len());
+        for item in items {
+            mapped.push(interpreter.call_value(&args[1], &[item])?);
+        }
+        Ok(Value::list(mapped))
+    });
+
+    // str(value) is the text print shows for value
+    interpreter.set_global_fn("str", Arity::exact(1), |interpreter, args| {
+        Ok(Value::String(interpreter.display(&args[0])?))
+    });
+
+    // gc() frees what only reference cycles keep alive, and is how many values that was
+    interpreter.set_global_fn("gc", Arity::exact(0), |interpreter, _| {
+        Ok(Value::Number(interpreter.collect_garbage() as f32))
+    });
+
+    interpreter.set_typed_fn("readFile", move |path: String| -> Result<String, Error> {
+        require(capabilities.fs_read, "readFile", "fs_read")?;
+        fs::read_to_string(path).map_err(io_error)
+    });
+
+    interpreter.set_typed_fn("writeFile", move |path: String, contents: String| -> Result<(), Error> {
+        require(capabilities.fs_write, "writeFile", "fs_write")?;
+        fs::write(path, contents).map_err(io_error)
+    });
+
+    // getEnv(name) is nil when the variable isn't set
+    interpreter.set_typed_fn("getEnv", move |name: String| -> Result<Option<String>, Error> {
+        require(capabilities.env, "getEnv", "env")?;
+        Ok(env::var(name).ok())
+    });
+
+    // exit(code) ends the process, 0 without a code
+    let exit_arity = Arity { min: 0, max: Some(1) };
+    interpreter.set_global_fn("exit", exit_arity, move |_, args| {
+        require(capabilities.exit, "exit", "exit")?;
+        match args.first() {
+            None => process::exit(0),
+            Some(Value::Number(code)) => process::exit(*code as i32),
+            Some(_) => Err(Error::new(
+                ErrorKind::WrongValueType("The exit code must be a number.".to_string()),
+                None,
+            )),
+        }
+    });
+
+    // random() is a number in [0, 1), from a xorshift generator seeded by the std hasher
+    let state = Cell::new(RandomState::new().hash_one(SystemTime::now()) | 1);
+    interpreter.set_typed_fn("random", move || -> Result<f32, Error> {
+        require(capabilities.random, "random", "random")?;
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        // 24 bits, as many as a Lox number holds, so it never rounds up to 1
+        Ok((x >> 40) as f32 / (1u64 << 24) as f32)
+    });
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::new(ErrorKind::IoError(error.to_string()), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use lox_interpreter::{Diagnostics, Engine, ErrorKind, Value};
+
+    use super::{set_stdlib_with, Capabilities};
+
+    fn engine_with(capabilities: Capabilities) -> Engine {
+        Engine::builder()
+            .module(|interpreter| set_stdlib_with(interpreter, capabilities))
+            .build()
+    }
+
+    #[test]
+    fn test_locked_down() {
+        let mut engine = engine_with(Capabilities::locked_down());
+        for call in [
+            "clock();",
+            "readFile(\"a\");",
+            "writeFile(\"a\", \"b\");",
+            "getEnv(\"HOME\");",
+            "exit(1);",
+            "random();",
+            "import \"a.lox\" as a;",
+        ] {
+            let Err(Diagnostics::Runtime(error)) = engine.eval(call) else {
+                panic!("{} should fail", call);
+            };
+            assert!(matches!(error.kind(), ErrorKind::PermissionDenied(_)), "{}", call);
+            assert!(error.location().is_some(), "{}", call);
+        }
+
+        // natives that don't reach the host are always there
+        assert!(engine.eval("map([1], x => x);").is_ok());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let path = env::temp_dir().join("lox_std_test.txt");
+        let path = path.to_string_lossy().replace('\\', "/");
+        let mut engine = engine_with(Capabilities {
+            fs_read: true,
+            fs_write: true,
+            ..Capabilities::locked_down()
+        });
+
+        let script = format!("writeFile(\"{0}\", \"saved\"); readFile(\"{0}\");", path);
+        assert!(matches!(engine.eval(&script), Ok(Value::String(s)) if s == "saved"));
+        let script = format!("writeFile(\"{0}\", \"export var saved = 1;\"); import \"{0}\" as m; m.saved;", path);
+        assert!(matches!(engine.eval(&script), Ok(Value::Number(n)) if n == 1.0));
+        assert!(matches!(engine.eval("random();"), Err(Diagnostics::Runtime(_))));
+
+        let mut engine = engine_with(Capabilities::all());
+        for _ in 0..100 {
+            assert!(matches!(engine.eval("random();"), Ok(Value::Number(n)) if (0.0..1.0).contains(&n)));
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::tokenizer::Token;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, From, Clone)]
 pub enum Error {
     #[from]
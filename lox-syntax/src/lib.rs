@@ -2,7 +2,8 @@ mod errors;
 mod parser;
 mod tokenizer;
 
-pub use parser::ast::{Expr, ExprVisitor, Stmt, StmtVisitor, Node};
+pub use parser::ast::{Expr, ExprVisitor, Stmt, StmtVisitor, Node, Param, Pattern, MatchArm, TypeAnnotation, Variant, VarKind};
+pub use errors::Error;
 pub use parser::ast_printer::TreePrinter;
 pub use parser::parse_program;
 pub use tokenizer::token::{Literal, Token, TokenType};
@@ -5,6 +5,100 @@ pub enum Node {
     Stmt(Box<Stmt>),
 }
 
+// A function parameter: `name`, `name = default` or a trailing `...name` collecting the rest
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Token,
+    pub default: Option<Expr>,
+    pub rest: bool,
+    pub annotation: Option<TypeAnnotation>,
+}
+
+// A type written after a name, `x: Number` or `x: Point?` when it can be nil too. Only the type
+// checker reads them, the interpreter ignores them.
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation {
+    pub name: Token,
+    pub nullable: bool,
+}
+
+// Patterns tested against a value in `match` arms
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // `_`
+    Wildcard,
+    Literal {
+        token: Token,
+        value: Literal,
+    },
+    Binding {
+        name: Token,
+    },
+    // `[first, second, ...rest]`
+    List {
+        bracket: Token,
+        elements: Vec<Pattern>,
+        rest: Option<Token>,
+    },
+    // `{name, age}`, binds the instance fields of the same names
+    Object {
+        brace: Token,
+        fields: Vec<Token>,
+    },
+    // `Point(x, y)`, matched against fields named after the parameters of the class initializer,
+    // an instance without them is an error
+    Instance {
+        class: Box<Expr>,
+        paren: Token,
+        fields: Vec<Pattern>,
+    },
+    // `Shape.Empty`, a qualified name compared for equality with the value
+    Constant {
+        value: Box<Expr>,
+    },
+}
+
+impl Pattern {
+    // names bound by the pattern, left to right; `..._` binds nothing
+    pub fn bindings(&self) -> Vec<&Token> {
+        match self {
+            Pattern::Wildcard | Pattern::Literal { .. } | Pattern::Constant { .. } => Vec::new(),
+            Pattern::Binding { name } => vec![name],
+            Pattern::List { elements, rest, .. } => elements
+                .iter()
+                .flat_map(Pattern::bindings)
+                .chain(rest.iter().filter(|rest| rest.to_string() != "_"))
+                .collect(),
+            Pattern::Object { fields, .. } => fields.iter().collect(),
+            Pattern::Instance { fields, .. } => fields.iter().flat_map(Pattern::bindings).collect(),
+        }
+    }
+}
+
+// An enum variant: `Circle(radius)` carries one value, `Empty` none
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Token,
+    pub fields: Vec<Token>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    // a `Stmt::Expression` gives the value of the match, other statements evaluate to nil
+    pub body: Box<Stmt>,
+}
+
+// How a variable was declared: `var` can be redeclared in the same scope, `let` can't, and a
+// `const` can't be assigned after its declaration either
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VarKind {
+    Var,
+    Let,
+    Const,
+}
+
 // Expression enum with all expression types as variants
 #[derive(Debug, Clone)]
 pub enum Expr {
@@ -12,6 +106,11 @@ pub enum Expr {
         name: Token,
         value: Box<Expr>,
     },
+    // `[a, b] = [b, a]`, every value is computed before any variable is assigned
+    AssignPattern {
+        pattern: Pattern,
+        value: Box<Expr>,
+    },
     Binary {
         left: Box<Expr>,
         operator: Token,
@@ -21,22 +120,40 @@ pub enum Expr {
         callee: Box<Expr>,
         paren: Token,
         arguments: Vec<Expr>,
+        // `name: value` arguments, always after the positional ones
+        named_arguments: Vec<(Token, Expr)>,
     },
     Comma {
         left: Box<Expr>,
         right: Box<Expr>,
     },
+    Conditional {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
     Get {
         object: Box<Expr>,
         name: Token,
+        optional: bool,
     },
     Grouping {
         expression: Box<Expr>,
     },
+    // `list[i]`, instances are indexed through their `__index__` method
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
     Lambda {
-        params: Vec<Token>,
+        params: Vec<Param>,
         body: Vec<Stmt>,
     },
+    List {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
     Literal {
         value: Literal,
     },
@@ -45,10 +162,28 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    // `obj::method` and `Class::method`, a method bound to the object it is read from
+    MethodRef {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Match {
+        keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    // `x++` and `obj.field--`, which evaluate to the value before the update
+    Postfix {
+        target: Box<Expr>,
+        // binary operator applied to the target and 1, `+` for `++` and `-` for `--`
+        operator: Token,
+    },
     Set {
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
+        // binary operator of a compound assignment, applied to the current field value
+        operator: Option<Token>,
     },
     Super {
         keyword: Token,
@@ -76,21 +211,57 @@ pub enum Stmt {
     Class {
         name: Token,
         superclass: Option<Box<Expr>>,
+        // traits composed with `with`, in declaration order
+        traits: Vec<Expr>,
         methods: Vec<Box<Stmt>>,
+        // property accessors, invoked on `obj.name` and `obj.name = value`
+        getters: Vec<Box<Stmt>>,
+        setters: Vec<Box<Stmt>>,
+        static_methods: Vec<Box<Stmt>>,
+        // `Stmt::Var` declarations of class-level fields
+        static_fields: Vec<Stmt>,
+        // `x: Number;`, the type of an instance field
+        fields: Vec<(Token, TypeAnnotation)>,
+    },
+    // a top-level declaration whose name other modules can import
+    Export {
+        name: Token,
+        declaration: Box<Stmt>,
+    },
+    Enum {
+        name: Token,
+        variants: Vec<Variant>,
     },
     Expression {
         expression: Expr,
     },
+    // `for (var x in iterable)`, over the elements of a list or the values of a generator
+    ForIn {
+        name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
     Function {
         name: Token,
-        params: Vec<Token>,
+        params: Vec<Param>,
         body: Vec<Stmt>,
+        return_type: Option<TypeAnnotation>,
+    },
+    Trait {
+        name: Token,
+        methods: Vec<Box<Stmt>>,
     },
     If {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
+    // `import "path" as alias;` binds the module, `import { a, b } from "path";` binds its exports
+    Import {
+        path: Token,
+        alias: Option<Token>,
+        names: Vec<Token>,
+    },
     Print {
         expression: Expr,
     },
@@ -101,11 +272,57 @@ pub enum Stmt {
     Var {
         name: Token,
         initializer: Option<Expr>,
+        kind: VarKind,
+        annotation: Option<TypeAnnotation>,
+    },
+    // `var [a, b, ...rest] = list;` and `var {name, age} = instance;`
+    VarPattern {
+        pattern: Pattern,
+        initializer: Expr,
+        kind: VarKind,
     },
     While {
         condition: Expr,
         body: Box<Stmt>,
     },
+    // suspends the generator running the enclosing function
+    Yield {
+        keyword: Token,
+        value: Option<Expr>,
+    },
+}
+
+impl Stmt {
+    // Whether a `yield` runs as part of this statement. Only yields reached through blocks, ifs
+    // and loops count, those nested in functions or expressions belong to something else.
+    pub fn yields(&self) -> bool {
+        match self {
+            Stmt::Yield { .. } => true,
+            Stmt::Block { statements } => statements.iter().any(Stmt::yields),
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            } => then_branch.yields() || else_branch.as_ref().is_some_and(|e| e.yields()),
+            Stmt::While { body, .. } | Stmt::ForIn { body, .. } => body.yields(),
+            _ => false,
+        }
+    }
+
+    // Whether every way through this statement ends in a `return`. A loop may not run its body,
+    // so only blocks and ifs with both branches count.
+    pub fn always_returns(&self) -> bool {
+        match self {
+            Stmt::Return { .. } => true,
+            Stmt::Block { statements } => statements.iter().any(Stmt::always_returns),
+            Stmt::If {
+                then_branch,
+                else_branch: Some(else_branch),
+                ..
+            } => then_branch.always_returns() && else_branch.always_returns(),
+            _ => false,
+        }
+    }
 }
 
 // Expression Visitor trait
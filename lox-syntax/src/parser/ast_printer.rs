@@ -6,6 +6,12 @@ pub struct TreePrinter {
     indent_level: usize,
 }
 
+impl Default for TreePrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TreePrinter {
     pub fn new() -> Self {
         Self { indent_level: 0 }
@@ -89,7 +95,7 @@ impl ExprVisitor<String> for TreePrinter {
                         Literal::Number(n) => n.to_string(),
                         Literal::String(s) => format!("\"{}\"", s),
                         Literal::Bool(b) => format!("\"{}\"", b),
-                        Literal::Null => format!("\"nil\""),
+                        Literal::Null => "\"nil\"".to_string(),
                     }
                 )
             }
@@ -132,10 +138,25 @@ impl ExprVisitor<String> for TreePrinter {
                 }));
                 result
             }
+            Expr::Postfix { target, operator } => {
+                let mut result = format!("{}Postfix\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!("{}operator: {}\n", printer.indent(), operator.token_type)
+                }));
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}target:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(target))
+                    )
+                }));
+                result
+            }
             Expr::Call {
                 callee,
                 paren: _,
                 arguments,
+                named_arguments,
             } => {
                 let mut result = format!("{}Call\n", self.indent());
                 result.push_str(&self.nested(|printer| {
@@ -157,6 +178,16 @@ impl ExprVisitor<String> for TreePrinter {
                             )
                         }));
                     }
+                    for (name, arg) in named_arguments {
+                        args_result.push_str(&printer.nested(|p| {
+                            format!(
+                                "{}{}:\n{}",
+                                p.indent(),
+                                name,
+                                p.nested(|p2| p2.visit_expr(arg))
+                            )
+                        }));
+                    }
                     args_result
                 }));
                 result
@@ -179,8 +210,89 @@ impl ExprVisitor<String> for TreePrinter {
                 }));
                 result
             }
-            Expr::Get { object, name } => {
-                let mut result = format!("{}Get\n", self.indent());
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut result = format!("{}Conditional\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}condition:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(condition))
+                    )
+                }));
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}then_branch:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(then_branch))
+                    )
+                }));
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}else_branch:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(else_branch))
+                    )
+                }));
+                result
+            }
+            Expr::AssignPattern { pattern, value } => {
+                let mut result = format!("{}AssignPattern\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!("{}pattern: {:?}\n", printer.indent(), pattern)
+                }));
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}value:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(value))
+                    )
+                }));
+                result
+            }
+            Expr::List { elements, .. } => {
+                let mut result = format!("{}List\n", self.indent());
+                for element in elements {
+                    result.push_str(&self.nested(|p| p.visit_expr(element)));
+                }
+                result
+            }
+            Expr::Match { subject, arms, .. } => {
+                let mut result = format!("{}Match\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}subject:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(subject))
+                    )
+                }));
+                for arm in arms {
+                    result.push_str(&self.nested(|printer| {
+                        let mut arm_str = format!("{}arm: {:?}\n", printer.indent(), arm.pattern);
+                        if let Some(guard) = &arm.guard {
+                            arm_str.push_str(&printer.nested(|p| {
+                                format!("{}guard:\n{}", p.indent(), p.nested(|p2| p2.visit_expr(guard)))
+                            }));
+                        }
+                        arm_str.push_str(&printer.nested(|p| p.visit_stmt(&arm.body)));
+                        arm_str
+                    }));
+                }
+                result
+            }
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => {
+                let mut result = format!(
+                    "{}{}\n",
+                    self.indent(),
+                    if *optional { "OptionalGet" } else { "Get" }
+                );
                 result.push_str(&self.nested(|printer| {
                     format!(
                         "{}object:\n{}",
@@ -195,12 +307,50 @@ impl ExprVisitor<String> for TreePrinter {
                 );
                 result
             }
+            Expr::Index { object, index, .. } => {
+                let mut result = format!("{}Index\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}object:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(object))
+                    )
+                }));
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}index:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(index))
+                    )
+                }));
+                result
+            }
+            Expr::MethodRef { object, name } => {
+                let mut result = format!("{}MethodRef\n", self.indent());
+                result.push_str(&self.nested(|printer| {
+                    format!(
+                        "{}object:\n{}",
+                        printer.indent(),
+                        printer.nested(|p| p.visit_expr(object))
+                    )
+                }));
+                result.push_str(
+                    &self.nested(|printer| format!("{}name: {}\n", printer.indent(), name)),
+                );
+                result
+            }
             Expr::Set {
                 object,
                 name,
                 value,
+                operator,
             } => {
                 let mut result = format!("{}Set\n", self.indent());
+                if let Some(operator) = operator {
+                    result.push_str(&self.nested(|printer| {
+                        format!("{}operator: {}\n", printer.indent(), operator.token_type)
+                    }));
+                }
                 result.push_str(&self.nested(|printer| {
                     format!(
                         "{}object:\n{}",
@@ -266,7 +416,7 @@ impl ExprVisitor<String> for TreePrinter {
                 result.push_str(&self.nested(|printer| {
                     let params_str = params
                         .iter()
-                        .map(|p| p.token_type.to_string())
+                        .map(|p| p.name.token_type.to_string())
                         .collect::<Vec<_>>()
                         .join(" ");
                     format!("{}params: {}\n", printer.indent(), params_str)
@@ -311,8 +461,13 @@ impl StmtVisitor<String> for TreePrinter {
                 }));
                 result
             }
-            Stmt::Var { name, initializer } => {
-                let mut result = format!("{}VarStmt\n", self.indent());
+            Stmt::Var {
+                name,
+                initializer,
+                kind,
+                ..
+            } => {
+                let mut result = format!("{}{:?}Stmt\n", self.indent(), kind);
                 result.push_str(&self.nested(|printer| {
                     format!(
                         "{}name: {}\n",
@@ -389,7 +544,7 @@ impl StmtVisitor<String> for TreePrinter {
 #[cfg(test)]
 mod tests {
     use crate::{
-        parser::ast::{Expr, Stmt, StmtVisitor},
+        parser::ast::{Expr, Stmt, StmtVisitor, VarKind},
         tokenizer::{token::Literal, Token, TokenType},
     };
 
@@ -404,6 +559,7 @@ mod tests {
                     token_type: TokenType::VAR,
                     literal: Some(Literal::String("x".to_string())),
                     line: 0,
+                    id: 0,
                 },
                 initializer: Some(Expr::Binary {
                     left: Box::new(Expr::Literal {
@@ -413,11 +569,14 @@ mod tests {
                         token_type: TokenType::PLUS,
                         literal: None,
                         line: 0,
+                        id: 0,
                     },
                     right: Box::new(Expr::Literal {
                         value: Literal::Number(2.0),
                     }),
                 }),
+                kind: VarKind::Var,
+                annotation: None,
             },
             Stmt::Print {
                 expression: Expr::Variable {
@@ -425,6 +584,7 @@ mod tests {
                         token_type: TokenType::VAR,
                         literal: Some(Literal::String("x".to_string())),
                         line: 0,
+                        id: 0,
                     },
                 },
             },
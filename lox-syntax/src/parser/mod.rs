@@ -8,6 +8,7 @@ use crate::{
 pub(crate) mod ast;
 pub(crate) mod ast_printer;
 mod token_stream;
+#[allow(clippy::module_inception)]
 pub(crate) mod parser;
 
 pub fn parse_program(tokens: &[Token]) -> Result<Vec<Stmt>> {
@@ -4,19 +4,25 @@ use crate::{
     Expr,
 };
 
-use super::{ast::Stmt, token_stream::TokenStream};
+use super::{
+    ast::{MatchArm, Param, Pattern, Stmt, TypeAnnotation, VarKind, Variant},
+    token_stream::TokenStream,
+};
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum FunctionKind {
     Function,
     Method,
     Getter,
+    Setter,
 }
 
 pub struct Parser<'a> {
     stream: TokenStream<'a>,
     errors: Vec<Error>,
     in_loop: bool,
+    // parsing a match guard, where `=>` belongs to the arm
+    in_guard: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -25,6 +31,7 @@ impl<'a> Parser<'a> {
             stream: TokenStream::new(tokens),
             errors: Vec::new(),
             in_loop: false,
+            in_guard: false,
         }
     }
 
@@ -63,8 +70,14 @@ impl<'a> Parser<'a> {
             // discard tokens until we have found a statement boundary
             match self.stream.advance().token_type {
                 TokenType::CLASS
+                | TokenType::TRAIT
+                | TokenType::ENUM
+                | TokenType::IMPORT
+                | TokenType::EXPORT
                 | TokenType::FUN
                 | TokenType::VAR
+                | TokenType::LET
+                | TokenType::CONST
                 | TokenType::FOR
                 | TokenType::IF
                 | TokenType::WHILE
@@ -92,6 +105,11 @@ impl<'a> Parser<'a> {
             }
         }
 
+        // errors that didn't abort a declaration
+        if self.has_errors() {
+            return Err(self.errors.remove(0));
+        }
+
         Ok(statements)
     }
 
@@ -116,31 +134,135 @@ impl<'a> Parser<'a> {
     }
 
     fn assignment(&mut self) -> Expr {
-        let expr = self.or();
+        let expr = self.conditional();
 
-        if self.stream.match_tokens(&[TokenType::EQUAL]) {
+        if self.stream.match_tokens(&[
+            TokenType::EQUAL,
+            TokenType::PLUS_EQUAL,
+            TokenType::MINUS_EQUAL,
+            TokenType::STAR_EQUAL,
+            TokenType::SLASH_EQUAL,
+        ]) {
             let equals = self.stream.previous();
             let value = self.assignment();
 
-            match expr {
-                Expr::Variable { name } => {
-                    return Expr::Assign {
-                        name,
-                        value: Box::new(value),
-                    }
-                }
-                Expr::Get { object, name } => {
-                    return Expr::Set {
-                        object,
-                        name,
-                        value: Box::new(value),
-                    }
+            let operator = match equals.token_type {
+                TokenType::PLUS_EQUAL => Some(TokenType::PLUS),
+                TokenType::MINUS_EQUAL => Some(TokenType::MINUS),
+                TokenType::STAR_EQUAL => Some(TokenType::STAR),
+                TokenType::SLASH_EQUAL => Some(TokenType::SLASH),
+                _ => None,
+            }
+            .map(|token_type| Token {
+                token_type,
+                literal: None,
+                line: equals.line,
+                id: equals.id,
+            });
+
+            return self.assign_to(expr, equals, operator, value);
+        }
+
+        expr
+    }
+
+    // Builds the assignment of `value` to `target`. With a compound `operator` the current value
+    // is combined with `value` first: variables desugar into a binary expression, while
+    // properties keep the operator on `Expr::Set` so the object is only evaluated once.
+    fn assign_to(
+        &mut self,
+        target: Expr,
+        equals: &Token,
+        operator: Option<Token>,
+        value: Expr,
+    ) -> Expr {
+        match target {
+            Expr::Variable { name } => {
+                let value = match operator {
+                    Some(operator) => Expr::Binary {
+                        left: Box::new(Expr::Variable { name: name.clone() }),
+                        operator,
+                        right: Box::new(value),
+                    },
+                    None => value,
+                };
+                Expr::Assign {
+                    name,
+                    value: Box::new(value),
                 }
-                _ => {
+            }
+            Expr::Get {
+                object,
+                name,
+                optional: false,
+            } => Expr::Set {
+                object,
+                name,
+                value: Box::new(value),
+                operator,
+            },
+            Expr::List { .. } if operator.is_none() => match self.assignment_pattern(&target) {
+                Some(pattern) => Expr::AssignPattern {
+                    pattern,
+                    value: Box::new(value),
+                },
+                None => {
                     self.error(equals, "Invalid assignment target.");
+                    target
                 }
+            },
+            _ => {
+                self.error(equals, "Invalid assignment target.");
+                target
             }
         }
+    }
+
+    // `++x` / `--x` are sugar for `x += 1` / `x -= 1`
+    fn increment(&mut self, target: Expr, operator: &Token) -> Expr {
+        self.assign_to(
+            target,
+            operator,
+            Some(increment_operator(operator)),
+            Expr::Literal {
+                value: Literal::Number(1.0),
+            },
+        )
+    }
+
+    fn conditional(&mut self) -> Expr {
+        let expr = self.coalesce();
+
+        if self.stream.match_tokens(&[TokenType::QUESTION]) {
+            let then_branch = self.assignment();
+            self.consume(
+                TokenType::COLON,
+                "Expect ':' after then branch of conditional expression.",
+            );
+            // right-associative: `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
+            let else_branch = self.conditional();
+            return Expr::Conditional {
+                condition: Box::new(expr),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            };
+        }
+
+        expr
+    }
+
+    fn coalesce(&mut self) -> Expr {
+        let mut expr = self.or();
+
+        while self.stream.match_tokens(&[TokenType::QUESTION_QUESTION]) {
+            let operator = self.stream.previous();
+            let right = self.or();
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
 
         expr
     }
@@ -205,7 +327,7 @@ impl<'a> Parser<'a> {
         ]) {
             let operator = self.stream.previous();
             self.error(
-                &operator,
+                operator,
                 format!("Missing left‐hand operand before '{}'", operator.token_type),
             );
             let _ = self.factor();
@@ -214,7 +336,7 @@ impl<'a> Parser<'a> {
             };
         }
 
-        let mut expr = self.term();
+        let mut expr = self.bit_or();
 
         while self.stream.match_tokens(&[
             TokenType::GREATER,
@@ -222,6 +344,73 @@ impl<'a> Parser<'a> {
             TokenType::LESS,
             TokenType::LESS_EQUAL,
         ]) {
+            let operator = self.stream.previous();
+            let right = self.bit_or();
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
+
+        expr
+    }
+
+    fn bit_or(&mut self) -> Expr {
+        let mut expr = self.bit_xor();
+
+        while self.stream.match_tokens(&[TokenType::PIPE]) {
+            let operator = self.stream.previous();
+            let right = self.bit_xor();
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
+
+        expr
+    }
+
+    fn bit_xor(&mut self) -> Expr {
+        let mut expr = self.bit_and();
+
+        while self.stream.match_tokens(&[TokenType::CARET]) {
+            let operator = self.stream.previous();
+            let right = self.bit_and();
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
+
+        expr
+    }
+
+    fn bit_and(&mut self) -> Expr {
+        let mut expr = self.shift();
+
+        while self.stream.match_tokens(&[TokenType::AMPERSAND]) {
+            let operator = self.stream.previous();
+            let right = self.shift();
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
+
+        expr
+    }
+
+    fn shift(&mut self) -> Expr {
+        let mut expr = self.term();
+
+        while self
+            .stream
+            .match_tokens(&[TokenType::LESS_LESS, TokenType::GREATER_GREATER])
+        {
             let operator = self.stream.previous();
             let right = self.term();
             expr = Expr::Binary {
@@ -242,7 +431,7 @@ impl<'a> Parser<'a> {
             // We saw `+` or `-` at the start of term() → report
             let operator = self.stream.previous();
             self.error(
-                &operator,
+                operator,
                 format!("Missing left‐hand operand before '{}'", operator.token_type),
             );
             let _ = self.factor();
@@ -272,11 +461,16 @@ impl<'a> Parser<'a> {
     fn factor(&mut self) -> Expr {
         if self
             .stream
-            .match_tokens(&[TokenType::SLASH, TokenType::STAR])
+            .match_tokens(&[
+                TokenType::SLASH,
+                TokenType::STAR,
+                TokenType::PERCENT,
+                TokenType::TILDE_SLASH,
+            ])
         {
             let operator = self.stream.previous();
             self.error(
-                &operator,
+                operator,
                 format!("Missing left‐hand operand before '{}'", operator.token_type),
             );
             let _ = self.factor();
@@ -287,10 +481,12 @@ impl<'a> Parser<'a> {
 
         let mut expr = self.unary();
 
-        while self
-            .stream
-            .match_tokens(&[TokenType::SLASH, TokenType::STAR])
-        {
+        while self.stream.match_tokens(&[
+            TokenType::SLASH,
+            TokenType::STAR,
+            TokenType::PERCENT,
+            TokenType::TILDE_SLASH,
+        ]) {
             let operator = self.stream.previous();
             let right = self.unary();
             expr = Expr::Binary {
@@ -306,7 +502,7 @@ impl<'a> Parser<'a> {
     fn unary(&mut self) -> Expr {
         if self
             .stream
-            .match_tokens(&[TokenType::BANG, TokenType::MINUS])
+            .match_tokens(&[TokenType::BANG, TokenType::MINUS, TokenType::TILDE])
         {
             let operator = self.stream.previous();
             let right = self.unary();
@@ -316,7 +512,59 @@ impl<'a> Parser<'a> {
             };
         }
 
-        self.call()
+        if self
+            .stream
+            .match_tokens(&[TokenType::PLUS_PLUS, TokenType::MINUS_MINUS])
+        {
+            let operator = self.stream.previous();
+            let target = self.unary();
+            return self.increment(target, operator);
+        }
+
+        self.power()
+    }
+
+    fn power(&mut self) -> Expr {
+        let expr = self.postfix();
+
+        if self.stream.match_tokens(&[TokenType::STAR_STAR]) {
+            let operator = self.stream.previous();
+            // right-associative, and binds tighter than a unary operator on its left: -2 ** 2 == -4
+            let right = self.unary();
+            return Expr::Binary {
+                left: Box::new(expr),
+                operator: operator.clone(),
+                right: Box::new(right),
+            };
+        }
+
+        expr
+    }
+
+    fn postfix(&mut self) -> Expr {
+        let expr = self.call();
+
+        if self
+            .stream
+            .match_tokens(&[TokenType::PLUS_PLUS, TokenType::MINUS_MINUS])
+        {
+            let operator = self.stream.previous();
+            return match expr {
+                Expr::Variable { .. }
+                | Expr::Get {
+                    optional: false, ..
+                } => Expr::Postfix {
+                    target: Box::new(expr),
+                    operator: increment_operator(operator),
+                },
+                _ => {
+                    self.error(operator, "Invalid assignment target.");
+                    expr
+                }
+            };
+        }
+
+        expr
     }
 
     fn call(&mut self) -> Expr {
@@ -325,13 +573,38 @@ impl<'a> Parser<'a> {
         loop {
             if self.stream.match_tokens(&[TokenType::LEFT_PAREN]) {
                 expr = self.finish_call(expr)
-            } else if self.stream.match_tokens(&[TokenType::DOT]) {
+            } else if self
+                .stream
+                .match_tokens(&[TokenType::DOT, TokenType::QUESTION_DOT])
+            {
+                let optional = self.stream.previous().token_type == TokenType::QUESTION_DOT;
                 let name = self
-                    .consume(TokenType::IDENTIFIER, "Expect property name after '.'.")
+                    .consume(
+                        TokenType::IDENTIFIER,
+                        format!("Expect property name after '{}'.", self.stream.previous().token_type),
+                    )
                     .unwrap();
                 expr = Expr::Get {
                     object: Box::new(expr),
                     name: name.clone(),
+                    optional,
+                }
+            } else if self.stream.match_tokens(&[TokenType::LEFT_BRACKET]) {
+                let bracket = self.stream.previous().clone();
+                let index = self.assignment();
+                self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.");
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                }
+            } else if self.stream.match_tokens(&[TokenType::COLON_COLON]) {
+                let Some(name) = self.consume(TokenType::IDENTIFIER, "Expect method name after '::'.") else {
+                    break;
+                };
+                expr = Expr::MethodRef {
+                    object: Box::new(expr),
+                    name: name.clone(),
                 }
             } else {
                 break;
@@ -342,38 +615,62 @@ impl<'a> Parser<'a> {
     }
 
     fn finish_call(&mut self, callee: Expr) -> Expr {
+        // lambdas can be passed to calls inside a match guard
+        let in_guard = std::mem::replace(&mut self.in_guard, false);
         let mut arguments = Vec::new();
+        let mut named_arguments: Vec<(Token, Expr)> = Vec::new();
         if !self.stream.check(TokenType::RIGHT_PAREN) {
             loop {
-                if arguments.len() >= 255 {
+                if arguments.len() + named_arguments.len() >= 255 {
                     self.error(
                         self.stream.peek_token(),
                         "Can't have more than 255 arguments.",
                     );
                 }
-                arguments.push(self.assignment());
+
+                if self.stream.check(TokenType::IDENTIFIER) && self.stream.check_next(TokenType::COLON) {
+                    // f(b: 3, a: 1)
+                    let name = self.stream.advance().clone();
+                    self.stream.advance();
+                    if named_arguments.iter().any(|(other, _)| other.to_string() == name.to_string()) {
+                        self.error(&name, format!("Argument '{}' is given more than once.", name));
+                    }
+                    named_arguments.push((name, self.assignment()));
+                } else {
+                    if !named_arguments.is_empty() {
+                        self.error(
+                            self.stream.peek_token(),
+                            "Positional arguments can't follow named arguments.",
+                        );
+                    }
+                    arguments.push(self.assignment());
+                }
+
                 if !self.stream.match_tokens(&[TokenType::COMMA]) {
                     break;
                 }
             }
         }
 
+        self.in_guard = in_guard;
+
         let mut paren = &Token {
             token_type: TokenType::INVALID,
             literal: None,
             line: 0,
+            id: 0,
         };
 
         if let Some(token) = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.") {
             paren = token;
         }
 
-        let temp = Expr::Call {
+        Expr::Call {
             callee: Box::new(callee),
             paren: paren.clone(),
             arguments,
-        };
-        temp
+            named_arguments,
+        }
     }
 
     fn primary(&mut self) -> Expr {
@@ -381,6 +678,10 @@ impl<'a> Parser<'a> {
             return self.lambda();
         }
 
+        if self.is_arrow_lambda() {
+            return self.arrow_lambda();
+        }
+
         if self.stream.match_tokens(&[TokenType::FALSE]) {
             return Expr::Literal {
                 value: Literal::Bool(false),
@@ -391,6 +692,11 @@ impl<'a> Parser<'a> {
                 value: Literal::Bool(true),
             };
         }
+        if self.stream.match_tokens(&[TokenType::NIL]) {
+            return Expr::Literal {
+                value: Literal::Null,
+            };
+        }
 
         if self
             .stream
@@ -409,6 +715,28 @@ impl<'a> Parser<'a> {
             return Expr::Super { keyword: keyword.clone(), method: method.clone() };
         }
 
+        if self.stream.match_tokens(&[TokenType::MATCH]) {
+            return self.match_expr().unwrap_or(Expr::Literal {
+                value: Literal::Null,
+            });
+        }
+
+        if self.stream.match_tokens(&[TokenType::LEFT_BRACKET]) {
+            let bracket = self.stream.previous().clone();
+            let mut elements = Vec::new();
+            if !self.stream.check(TokenType::RIGHT_BRACKET) {
+                loop {
+                    elements.push(self.assignment());
+                    if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after list elements.");
+            return Expr::List { bracket, elements };
+        }
+
         if self.stream.match_tokens(&[TokenType::THIS]) {
             return Expr::This {
                 keyword: self.stream.previous().clone(),
@@ -447,29 +775,194 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn lambda(&mut self) -> Expr {
-        // parameters parsing
-        self.consume(TokenType::LEFT_PAREN, "Expected '(' after function name");
-        let mut params = Vec::new();
-        if !self.stream.check(TokenType::RIGHT_PAREN) {
-            loop {
-                if params.len() >= 255 {
-                    self.error(
-                        self.stream.peek_token(),
-                        "Can't have more than 255 parameters.",
-                    );
+    fn match_expr(&mut self) -> Option<Expr> {
+        let keyword = self.stream.previous().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'match'.")?;
+        let subject = self.expression();
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after match value.")?;
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before match arms.")?;
+
+        let mut arms = Vec::new();
+        while !self.stream.check(TokenType::RIGHT_BRACE) && !self.stream.is_eof() {
+            let pattern = self.pattern()?;
+
+            let mut guard = None;
+            if self.stream.match_tokens(&[TokenType::IF]) {
+                // `if x == y => ...` ends the guard at the arrow, it doesn't start a lambda
+                self.in_guard = true;
+                guard = Some(self.assignment());
+                self.in_guard = false;
+            }
+
+            self.consume(TokenType::EQUAL_GREATER, "Expect '=>' after match pattern.")?;
+            let body = if self.stream.match_tokens(&[TokenType::LEFT_BRACE]) {
+                // commas are optional after a block or a statement
+                let statements = self.block();
+                self.stream.match_tokens(&[TokenType::COMMA]);
+                Stmt::Block { statements }
+            } else if self.stream.check(TokenType::PRINT)
+                || self.stream.check(TokenType::RETURN)
+                || self.stream.check(TokenType::BREAK)
+            {
+                let statement = self.statement()?;
+                self.stream.match_tokens(&[TokenType::COMMA]);
+                statement
+            } else {
+                let expression = self.assignment();
+                if !self.stream.check(TokenType::RIGHT_BRACE) {
+                    self.consume(TokenType::COMMA, "Expect ',' after match arm.")?;
                 }
+                Stmt::Expression { expression }
+            };
 
-                let temp = self
-                    .consume(TokenType::IDENTIFIER, "Expect parameter name.")
-                    .unwrap();
-                params.push(temp.clone());
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body: Box::new(body),
+            });
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after match arms.")?;
+        Some(Expr::Match {
+            keyword,
+            subject: Box::new(subject),
+            arms,
+        })
+    }
+
+    fn pattern(&mut self) -> Option<Pattern> {
+        if self.stream.match_tokens(&[
+            TokenType::NUMBER,
+            TokenType::STRING,
+            TokenType::TRUE,
+            TokenType::FALSE,
+            TokenType::NIL,
+        ]) {
+            let token = self.stream.previous().clone();
+            let value = match token.token_type {
+                TokenType::TRUE => Literal::Bool(true),
+                TokenType::FALSE => Literal::Bool(false),
+                TokenType::NIL => Literal::Null,
+                _ => token.literal.clone()?,
+            };
+            return Some(Pattern::Literal { token, value });
+        }
+
+        if self.stream.match_tokens(&[TokenType::MINUS]) {
+            let token = self.consume(TokenType::STRING, "Expect number after '-' in pattern.")?;
+            return match token.literal {
+                Some(Literal::Number(n)) => Some(Pattern::Literal {
+                    token: token.clone(),
+                    value: Literal::Number(-n),
+                }),
+                _ => {
+                    self.error(token, "Expect number after '-' in pattern.");
+                    None
+                }
+            };
+        }
+
+        if self.stream.match_tokens(&[TokenType::LEFT_BRACE]) {
+            let brace = self.stream.previous().clone();
+            let mut fields = Vec::new();
+            if !self.stream.check(TokenType::RIGHT_BRACE) {
+                loop {
+                    fields.push(self.consume(TokenType::IDENTIFIER, "Expect field name.")?.clone());
+                    if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(TokenType::RIGHT_BRACE, "Expect '}' after field names.")?;
+            return Some(Pattern::Object { brace, fields });
+        }
+
+        if self.stream.match_tokens(&[TokenType::LEFT_BRACKET]) {
+            let bracket = self.stream.previous().clone();
+            let mut elements = Vec::new();
+            let mut rest = None;
+            if !self.stream.check(TokenType::RIGHT_BRACKET) {
+                loop {
+                    if self.stream.match_tokens(&[TokenType::ELLIPSIS]) {
+                        rest = Some(
+                            self.consume(TokenType::IDENTIFIER, "Expect name after '...'.")?
+                                .clone(),
+                        );
+                        break;
+                    }
+
+                    elements.push(self.pattern()?);
+                    if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(
+                TokenType::RIGHT_BRACKET,
+                "Expect ']' after list pattern, a rest element must be the last one.",
+            )?;
+            return Some(Pattern::List {
+                bracket,
+                elements,
+                rest,
+            });
+        }
+
+        let name = self.consume(TokenType::IDENTIFIER, "Expect pattern.")?.clone();
+        if name.to_string() == "_" {
+            return Some(Pattern::Wildcard);
+        }
+
+        if !self.stream.check(TokenType::LEFT_PAREN) && !self.stream.check(TokenType::DOT) {
+            return Some(Pattern::Binding { name });
+        }
 
+        // Point(x, y), module.Point(x, y) or Shape.Circle(r)
+        let mut class = Expr::Variable { name };
+        while self.stream.match_tokens(&[TokenType::DOT]) {
+            class = Expr::Get {
+                object: Box::new(class),
+                name: self
+                    .consume(TokenType::IDENTIFIER, "Expect class name after '.'.")?
+                    .clone(),
+                optional: false,
+            };
+        }
+
+        // Shape.Empty
+        if !self.stream.check(TokenType::LEFT_PAREN) {
+            return Some(Pattern::Constant {
+                value: Box::new(class),
+            });
+        }
+
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after class name in pattern.")?;
+        let mut fields = Vec::new();
+        if !self.stream.check(TokenType::RIGHT_PAREN) {
+            loop {
+                fields.push(self.pattern()?);
                 if !self.stream.match_tokens(&[TokenType::COMMA]) {
                     break;
                 }
             }
         }
+        let paren = self
+            .consume(TokenType::RIGHT_PAREN, "Expect ')' after field patterns.")?
+            .clone();
+
+        Some(Pattern::Instance {
+            class: Box::new(class),
+            paren,
+            fields,
+        })
+    }
+
+    fn lambda(&mut self) -> Expr {
+        // parameters parsing
+        self.consume(TokenType::LEFT_PAREN, "Expected '(' after function name");
+        let params = self.parameters().unwrap_or_default();
 
         self.consume(
             TokenType::RIGHT_PAREN,
@@ -478,26 +971,98 @@ impl<'a> Parser<'a> {
 
         // body
         self.consume(TokenType::LEFT_BRACE, "Expected '{' before function body");
-        let body = self.block();
+        let body = self.function_body();
 
         Expr::Lambda { params, body }
     }
 
+    // `x => ...` or a parenthesized parameter list followed by `=>`
+    fn is_arrow_lambda(&self) -> bool {
+        if self.in_guard {
+            return false;
+        }
+        let is_arrow = |token: Option<&Token>| token.is_some_and(|t| t.token_type == TokenType::EQUAL_GREATER);
+        if self.stream.check(TokenType::IDENTIFIER) {
+            return is_arrow(self.stream.peek_at(1));
+        }
+        if !self.stream.check(TokenType::LEFT_PAREN) {
+            return false;
+        }
+
+        let mut depth = 0;
+        let mut offset = 0;
+        while let Some(token) = self.stream.peek_at(offset) {
+            match token.token_type {
+                TokenType::LEFT_PAREN => depth += 1,
+                TokenType::RIGHT_PAREN => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return is_arrow(self.stream.peek_at(offset + 1));
+                    }
+                }
+                _ => {}
+            }
+            offset += 1;
+        }
+        false
+    }
+
+    // `(x, y) => x + y` returns the expression, `(x) => { ... }` has a full body
+    fn arrow_lambda(&mut self) -> Expr {
+        let params = if self.stream.match_tokens(&[TokenType::IDENTIFIER]) {
+            vec![Param {
+                name: self.stream.previous().clone(),
+                default: None,
+                rest: false,
+                annotation: None,
+            }]
+        } else {
+            self.consume(TokenType::LEFT_PAREN, "Expect '(' before lambda parameters.");
+            let params = self.parameters().unwrap_or_default();
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after lambda parameters.");
+            params
+        };
+
+        let arrow = self.stream.advance().clone();
+        if self.stream.match_tokens(&[TokenType::LEFT_BRACE]) {
+            let body = self.function_body();
+            return Expr::Lambda { params, body };
+        }
+
+        let value = self.assignment();
+        Expr::Lambda {
+            params,
+            body: vec![Stmt::Return {
+                keyword: arrow,
+                value: Some(value),
+            }],
+        }
+    }
+
     // ----- Statement parsing methods -----
 
     fn declaration(&mut self) -> Option<Stmt> {
-        let result = if self.stream.match_tokens(&[TokenType::VAR]) {
+        let errors = self.errors.len();
+        let result = if self.stream.match_tokens(&[TokenType::VAR, TokenType::LET, TokenType::CONST]) {
             self.var_declaration()
         } else if self.stream.match_tokens(&[TokenType::FUN]) {
             self.fun_declaration(FunctionKind::Function)
         } else if self.stream.match_tokens(&[TokenType::CLASS]) {
             self.class_declaration()
+        } else if self.stream.match_tokens(&[TokenType::TRAIT]) {
+            self.trait_declaration()
+        } else if self.stream.match_tokens(&[TokenType::ENUM]) {
+            self.enum_declaration()
+        } else if self.stream.match_tokens(&[TokenType::IMPORT]) {
+            self.import_declaration()
+        } else if self.stream.match_tokens(&[TokenType::EXPORT]) {
+            self.export_declaration()
         } else {
             self.statement()
         };
 
-        // If we got an error (None), then synchronize
-        if result.is_none() && self.has_errors() {
+        // If we got an error, then synchronize
+        if self.errors.len() > errors {
             self.synchronize();
         }
 
@@ -535,9 +1100,21 @@ impl<'a> Parser<'a> {
             return self.break_stmt();
         }
 
+        if self.stream.match_tokens(&[TokenType::YIELD]) {
+            return self.yield_stmt();
+        }
+
         self.expr_stmt()
     }
 
+    // a loop around a function doesn't reach into its body
+    fn function_body(&mut self) -> Vec<Stmt> {
+        let enclosing_loop = std::mem::replace(&mut self.in_loop, false);
+        let body = self.block();
+        self.in_loop = enclosing_loop;
+        body
+    }
+
     fn block(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
         while !self.stream.is_eof() && !self.stream.check(TokenType::RIGHT_BRACE) {
@@ -556,10 +1133,46 @@ impl<'a> Parser<'a> {
             );
         }
 
-        return statements;
+        statements
+    }
+
+    // A list literal on the left of `=` is read back as a pattern of the variables it lists.
+    fn assignment_pattern(&self, target: &Expr) -> Option<Pattern> {
+        match target {
+            Expr::Variable { name } if name.to_string() == "_" => Some(Pattern::Wildcard),
+            Expr::Variable { name } => Some(Pattern::Binding { name: name.clone() }),
+            Expr::List { bracket, elements } => Some(Pattern::List {
+                bracket: bracket.clone(),
+                elements: elements
+                    .iter()
+                    .map(|element| self.assignment_pattern(element))
+                    .collect::<Option<Vec<_>>>()?,
+                rest: None,
+            }),
+            _ => None,
+        }
     }
 
+    // after the `var`, `let` or `const` keyword
     fn var_declaration(&mut self) -> Option<Stmt> {
+        let kind = match self.stream.previous().token_type {
+            TokenType::LET => VarKind::Let,
+            TokenType::CONST => VarKind::Const,
+            _ => VarKind::Var,
+        };
+
+        if self.stream.check(TokenType::LEFT_BRACKET) || self.stream.check(TokenType::LEFT_BRACE) {
+            let pattern = self.pattern()?;
+            self.consume(TokenType::EQUAL, "Expect '=' after destructuring pattern.")?;
+            let initializer = self.expression();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after variable declaration.")?;
+            return Some(Stmt::VarPattern {
+                pattern,
+                initializer,
+                kind,
+            });
+        }
+
         let name = match self.stream.check(TokenType::IDENTIFIER) {
             true => self.stream.advance(),
             false => {
@@ -568,10 +1181,14 @@ impl<'a> Parser<'a> {
             }
         };
 
+        let annotation = self.annotation()?;
         let initializer = match self.stream.match_tokens(&[TokenType::EQUAL]) {
             true => Some(self.expression()),
             false => None,
         };
+        if kind == VarKind::Const && initializer.is_none() {
+            self.error(name, format!("Constant '{}' must be initialized.", name));
+        }
 
         if self.stream.check(TokenType::SEMICOLON) {
             self.stream.advance();
@@ -586,48 +1203,29 @@ impl<'a> Parser<'a> {
         Some(Stmt::Var {
             name: name.clone(),
             initializer,
+            kind,
+            annotation,
         })
     }
 
     fn fun_declaration(&mut self, kind: FunctionKind) -> Option<Stmt> {
         // function name
         let name = self.consume(TokenType::IDENTIFIER, format!("Expect {:?} name.", kind))?;
-        let mut kind = kind;
 
-        // parameters parsing
-        if kind != FunctionKind::Method {
-            self.consume(
-                TokenType::LEFT_PAREN,
-                format!("Expected '(' after {:?} name", kind),
-            );
-        } else {
-            match self.stream.check(TokenType::LEFT_PAREN) {
-                true => {
-                    self.stream.advance();
-                }
-                false => kind = FunctionKind::Getter,
-            }
+        // setters are declared as `name=(value) { ... }`
+        if kind == FunctionKind::Setter {
+            self.consume(TokenType::EQUAL, "Expected '=' after Setter name");
         }
 
+        // parameters parsing, getters have no parameter list at all
         let mut params = Vec::new();
         if kind != FunctionKind::Getter {
-            if !self.stream.check(TokenType::RIGHT_PAREN) {
-                loop {
-                    if params.len() >= 255 {
-                        self.error(
-                            self.stream.peek_token(),
-                            "Can't have more than 255 parameters.",
-                        );
-                    }
-
-                    let temp = self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?;
-                    params.push(temp.clone());
+            self.consume(
+                TokenType::LEFT_PAREN,
+                format!("Expected '(' after {:?} name", kind),
+            );
 
-                    if !self.stream.match_tokens(&[TokenType::COMMA]) {
-                        break;
-                    }
-                }
-            }
+            params = self.parameters()?;
 
             self.consume(
                 TokenType::RIGHT_PAREN,
@@ -635,20 +1233,79 @@ impl<'a> Parser<'a> {
             );
         }
 
+        if kind == FunctionKind::Setter
+            && (params.len() != 1 || params[0].rest || params[0].default.is_some())
+        {
+            self.error(name, "A setter must take exactly one parameter.");
+        }
+
+        // fun f(): Bool { ... }
+        let return_type = self.annotation()?;
+
         // body
         self.consume(
             TokenType::LEFT_BRACE,
             format!("Expected '{{' before {:?} body", kind),
         );
-        let body = self.block();
+        let body = self.function_body();
 
         Some(Stmt::Function {
             name: name.clone(),
             params,
             body,
+            return_type,
         })
     }
 
+    // Parameters up to the closing parenthesis. Defaults can only be followed by other defaults
+    // and a rest parameter must come last.
+    fn parameters(&mut self) -> Option<Vec<Param>> {
+        let mut params: Vec<Param> = Vec::new();
+        if self.stream.check(TokenType::RIGHT_PAREN) {
+            return Some(params);
+        }
+
+        loop {
+            if params.len() >= 255 {
+                self.error(
+                    self.stream.peek_token(),
+                    "Can't have more than 255 parameters.",
+                );
+            }
+
+            if params.last().is_some_and(|p| p.rest) {
+                self.error(self.stream.peek_token(), "A rest parameter must be the last one.");
+            }
+
+            let rest = self.stream.match_tokens(&[TokenType::ELLIPSIS]);
+            let name = self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?.clone();
+
+            let annotation = self.annotation()?;
+            let mut default = None;
+            if self.stream.match_tokens(&[TokenType::EQUAL]) {
+                if rest {
+                    self.error(&name, "A rest parameter can't have a default value.");
+                }
+                default = Some(self.assignment());
+            } else if !rest && params.last().is_some_and(|p| p.default.is_some()) {
+                self.error(&name, "Parameter without a default value can't follow one with a default.");
+            }
+
+            params.push(Param {
+                name,
+                default,
+                rest,
+                annotation,
+            });
+
+            if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+
+        Some(params)
+    }
+
     fn class_declaration(&mut self) -> Option<Stmt> {
         let name = self.consume(TokenType::IDENTIFIER, "Expect class name.")?;
 
@@ -662,18 +1319,255 @@ impl<'a> Parser<'a> {
             }));
         }
 
+        // composed traits
+        let mut traits = Vec::new();
+        if self.stream.match_tokens(&[TokenType::WITH]) {
+            loop {
+                traits.push(Expr::Variable {
+                    name: self
+                        .consume(TokenType::IDENTIFIER, "Expect trait name.")
+                        .cloned()?,
+                });
+
+                if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
         self.consume(TokenType::LEFT_BRACE, "Expected '{' after class name");
 
         let mut methods = Vec::new();
+        let mut getters = Vec::new();
+        let mut setters = Vec::new();
+        let mut static_methods = Vec::new();
+        let mut static_fields = Vec::new();
+        let mut fields = Vec::new();
         while !self.stream.check(TokenType::RIGHT_BRACE) && !self.stream.is_eof() {
-            methods.push(Box::new(self.fun_declaration(FunctionKind::Method)?));
+            if self.stream.match_tokens(&[TokenType::STATIC]) {
+                if self.stream.check_next(TokenType::EQUAL)
+                    || self.stream.check_next(TokenType::SEMICOLON)
+                    || self.stream.check_next(TokenType::COLON)
+                {
+                    // static count = 0;
+                    static_fields.push(self.var_declaration()?);
+                } else if self.stream.check_next(TokenType::LEFT_BRACE) {
+                    self.error(self.stream.peek_token(), "Getters can't be static.");
+                    return None;
+                } else {
+                    static_methods.push(Box::new(self.fun_declaration(FunctionKind::Method)?));
+                }
+            } else if self.stream.check_next(TokenType::COLON) {
+                // x: Number; or a getter with its type, area: Number { ... }
+                let name = self.stream.advance().clone();
+                let annotation = self.annotation()?;
+                if self.stream.match_tokens(&[TokenType::LEFT_BRACE]) {
+                    getters.push(Box::new(Stmt::Function {
+                        name,
+                        params: Vec::new(),
+                        body: self.function_body(),
+                        return_type: annotation,
+                    }));
+                } else {
+                    self.consume(TokenType::SEMICOLON, "Expect ';' or a getter body after field type.")?;
+                    fields.push((name, annotation?));
+                }
+            } else if self.stream.check_next(TokenType::LEFT_BRACE) {
+                // area { ... }
+                getters.push(Box::new(self.fun_declaration(FunctionKind::Getter)?));
+            } else if self.stream.check_next(TokenType::EQUAL) {
+                // area=(value) { ... }
+                setters.push(Box::new(self.fun_declaration(FunctionKind::Setter)?));
+            } else {
+                methods.push(Box::new(self.fun_declaration(FunctionKind::Method)?));
+            }
         }
 
         self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.");
         Some(Stmt::Class {
             name: name.clone(),
             superclass,
+            traits,
             methods,
+            getters,
+            setters,
+            static_methods,
+            static_fields,
+            fields,
+        })
+    }
+
+    // `: Type` or `: Type?`, when there is a colon
+    fn annotation(&mut self) -> Option<Option<TypeAnnotation>> {
+        if !self.stream.match_tokens(&[TokenType::COLON]) {
+            return Some(None);
+        }
+
+        let name = self.consume(TokenType::IDENTIFIER, "Expect type after ':'.")?.clone();
+        let nullable = self.stream.match_tokens(&[TokenType::QUESTION]);
+        Some(Some(TypeAnnotation { name, nullable }))
+    }
+
+    fn trait_declaration(&mut self) -> Option<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expect trait name.")?;
+        self.consume(TokenType::LEFT_BRACE, "Expected '{' after trait name");
+
+        let mut methods = Vec::new();
+        while !self.stream.check(TokenType::RIGHT_BRACE) && !self.stream.is_eof() {
+            methods.push(Box::new(self.fun_declaration(FunctionKind::Method)?));
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after trait body.");
+        Some(Stmt::Trait {
+            name: name.clone(),
+            methods,
+        })
+    }
+
+    fn enum_declaration(&mut self) -> Option<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expect enum name.")?.clone();
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' after enum name.")?;
+
+        let mut variants: Vec<Variant> = Vec::new();
+        while !self.stream.check(TokenType::RIGHT_BRACE) && !self.stream.is_eof() {
+            let variant = self.consume(TokenType::IDENTIFIER, "Expect variant name.")?.clone();
+            if variants.iter().any(|v| v.name == variant) {
+                self.error(
+                    &variant,
+                    format!("Variant '{}' is already declared in enum '{}'.", variant, name),
+                );
+            }
+
+            let mut fields = Vec::new();
+            if self.stream.match_tokens(&[TokenType::LEFT_PAREN]) {
+                if !self.stream.check(TokenType::RIGHT_PAREN) {
+                    loop {
+                        fields.push(self.consume(TokenType::IDENTIFIER, "Expect field name.")?.clone());
+                        if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RIGHT_PAREN, "Expect ')' after variant fields.")?;
+            }
+            variants.push(Variant {
+                name: variant,
+                fields,
+            });
+
+            if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after enum variants.")?;
+        Some(Stmt::Enum { name, variants })
+    }
+
+    fn import_declaration(&mut self) -> Option<Stmt> {
+        // import { a, b } from "path";
+        let mut names = Vec::new();
+        if self.stream.match_tokens(&[TokenType::LEFT_BRACE]) {
+            loop {
+                names.push(
+                    self.consume(TokenType::IDENTIFIER, "Expect imported name.")
+                        .cloned()?,
+                );
+
+                if !self.stream.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+
+            self.consume(TokenType::RIGHT_BRACE, "Expect '}' after imported names.")?;
+            if !self.match_word("from") {
+                self.error(self.stream.peek_token(), "Expect 'from' after imported names.");
+                return None;
+            }
+        }
+
+        let path = self.module_path()?;
+
+        // import "path" as alias;
+        let mut alias = None;
+        if names.is_empty() {
+            if !self.match_word("as") {
+                self.error(self.stream.peek_token(), "Expect 'as' after module path.");
+                return None;
+            }
+            alias = Some(
+                self.consume(TokenType::IDENTIFIER, "Expect module name after 'as'.")
+                    .cloned()?,
+            );
+        }
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after import.")?;
+        Some(Stmt::Import {
+            path,
+            alias,
+            names,
+        })
+    }
+
+    fn module_path(&mut self) -> Option<Token> {
+        let token = self.consume(TokenType::STRING, "Expect module path string.")?;
+        match &token.literal {
+            Some(Literal::String(_)) => Some(token.clone()),
+            _ => {
+                self.error(token, "Expect module path string.");
+                None
+            }
+        }
+    }
+
+    // `as` and `from` are only keywords inside an import
+    fn match_word(&mut self, word: &str) -> bool {
+        let token = self.stream.peek_token();
+        if token.token_type == TokenType::IDENTIFIER && token.to_string() == word {
+            self.stream.advance();
+            return true;
+        }
+
+        false
+    }
+
+    fn export_declaration(&mut self) -> Option<Stmt> {
+        let declaration = if self.stream.match_tokens(&[TokenType::VAR, TokenType::LET, TokenType::CONST]) {
+            self.var_declaration()?
+        } else if self.stream.match_tokens(&[TokenType::FUN]) {
+            self.fun_declaration(FunctionKind::Function)?
+        } else if self.stream.match_tokens(&[TokenType::CLASS]) {
+            self.class_declaration()?
+        } else if self.stream.match_tokens(&[TokenType::TRAIT]) {
+            self.trait_declaration()?
+        } else if self.stream.match_tokens(&[TokenType::ENUM]) {
+            self.enum_declaration()?
+        } else {
+            self.error(
+                self.stream.peek_token(),
+                "Expect declaration after 'export'.",
+            );
+            return None;
+        };
+
+        let name = match &declaration {
+            Stmt::Var { name, .. }
+            | Stmt::Function { name, .. }
+            | Stmt::Class { name, .. }
+            | Stmt::Trait { name, .. }
+            | Stmt::Enum { name, .. } => name.clone(),
+            _ => {
+                self.error(
+                    self.stream.previous(),
+                    "Can't export a destructuring declaration.",
+                );
+                return None;
+            }
+        };
+
+        Some(Stmt::Export {
+            name,
+            declaration: Box::new(declaration),
         })
     }
 
@@ -717,14 +1611,21 @@ impl<'a> Parser<'a> {
 
     fn for_stmt(&mut self) -> Option<Stmt> {
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.");
-        let mut initializer = None;
-        if self.stream.match_tokens(&[TokenType::SEMICOLON]) {
-            initializer = None;
-        } else if self.stream.match_tokens(&[TokenType::VAR]) {
-            initializer = self.var_declaration();
+        let initializer = if self.stream.match_tokens(&[TokenType::SEMICOLON]) {
+            None
+        } else if self.stream.match_tokens(&[TokenType::VAR, TokenType::LET]) {
+            if self.stream.check(TokenType::IDENTIFIER)
+                && self
+                    .stream
+                    .peek_at(1)
+                    .is_some_and(|t| t.token_type == TokenType::IDENTIFIER && t.to_string() == "in")
+            {
+                return self.for_in_stmt();
+            }
+            self.var_declaration()
         } else {
-            initializer = self.expr_stmt();
-        }
+            self.expr_stmt()
+        };
 
         let mut condition = None;
         if !self.stream.match_tokens(&[TokenType::SEMICOLON]) {
@@ -738,7 +1639,10 @@ impl<'a> Parser<'a> {
         }
 
         self.consume(TokenType::RIGHT_PAREN, "Except ')' after for clauses.");
+        let enclosing_loop = self.in_loop;
+        self.in_loop = true;
         let mut body = self.statement();
+        self.in_loop = enclosing_loop;
 
         if let Some(increment) = increment {
             body = Some(Stmt::Block {
@@ -769,6 +1673,37 @@ impl<'a> Parser<'a> {
         body
     }
 
+    // for (var name in iterable) body, `in` is only a keyword here
+    fn for_in_stmt(&mut self) -> Option<Stmt> {
+        let name = self.stream.advance().clone();
+        self.stream.advance();
+        let iterable = self.expression();
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+        let enclosing_loop = self.in_loop;
+        self.in_loop = true;
+        let body = self.statement();
+        self.in_loop = enclosing_loop;
+        let body = body?;
+
+        Some(Stmt::ForIn {
+            name,
+            iterable,
+            body: Box::new(body),
+        })
+    }
+
+    fn yield_stmt(&mut self) -> Option<Stmt> {
+        let keyword = self.stream.previous().clone();
+        let value = if self.stream.check(TokenType::SEMICOLON) {
+            None
+        } else {
+            Some(self.expression())
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after yield value.")?;
+        Some(Stmt::Yield { keyword, value })
+    }
+
     fn if_stmt(&mut self) -> Option<Stmt> {
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.");
         let condition = self.expression();
@@ -802,6 +1737,11 @@ impl<'a> Parser<'a> {
     fn expr_stmt(&mut self) -> Option<Stmt> {
         let expression = self.expression();
 
+        // a match used as a statement doesn't need the semicolon
+        if matches!(expression, Expr::Match { .. }) && !self.stream.check(TokenType::SEMICOLON) {
+            return Some(Stmt::Expression { expression });
+        }
+
         self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
         Some(Stmt::Expression { expression })
     }
@@ -819,3 +1759,17 @@ impl<'a> Parser<'a> {
         Some(Stmt::Break)
     }
 }
+
+// the binary operator `++` and `--` apply to their operand and 1
+fn increment_operator(operator: &Token) -> Token {
+    let token_type = match operator.token_type {
+        TokenType::PLUS_PLUS => TokenType::PLUS,
+        _ => TokenType::MINUS,
+    };
+    Token {
+        token_type,
+        literal: None,
+        line: operator.line,
+        id: operator.id,
+    }
+}
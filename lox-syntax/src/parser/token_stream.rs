@@ -17,6 +17,7 @@ impl<'a> TokenStream<'a> {
                 token_type: TokenType::EOF,
                 literal: None,
                 line: 0,
+                id: 0,
             },
         }
     }
@@ -29,6 +30,18 @@ impl<'a> TokenStream<'a> {
         self.peek() == token_type
     }
 
+    // looks one token past the current one
+    pub fn check_next(&self, token_type: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|t| t.token_type == token_type)
+    }
+
+    // the token `offset` places after the current one
+    pub fn peek_at(&self, offset: usize) -> Option<&'a Token> {
+        self.tokens.get(self.current + offset)
+    }
+
     pub fn is_eof(&self) -> bool {
         self.check(TokenType::EOF)
     }
@@ -44,6 +57,7 @@ impl<'a> TokenStream<'a> {
                 token_type: TokenType::EOF,
                 literal: None,
                 line: 0,
+                id: 0,
             },
         }
     }
@@ -55,6 +69,7 @@ impl<'a> TokenStream<'a> {
                 token_type: TokenType::INVALID,
                 literal: None,
                 line: 0,
+                id: 0,
             },
         }
     }
@@ -1,7 +1,11 @@
 mod position;
 pub(crate) mod token;
 
-use std::{iter::Peekable, str::Chars};
+use std::{
+    iter::Peekable,
+    str::Chars,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use phf::phf_map;
 use position::BytePos;
@@ -13,22 +17,35 @@ static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map!(
     "and" => TokenType::AND,
     "break" => TokenType::BREAK,
     "class" => TokenType::CLASS,
+    "const" => TokenType::CONST,
     "else" => TokenType::ELSE,
+    "enum" => TokenType::ENUM,
+    "export" => TokenType::EXPORT,
     "false" => TokenType::FALSE,
     "for" => TokenType::FOR,
     "fun" => TokenType::FUN,
     "if" => TokenType::IF,
+    "import" => TokenType::IMPORT,
+    "let" => TokenType::LET,
+    "match" => TokenType::MATCH,
     "nil" => TokenType::NIL,
     "or" => TokenType::OR,
     "print" => TokenType::PRINT,
     "return" => TokenType::RETURN,
+    "static" => TokenType::STATIC,
     "super" => TokenType::SUPER,
     "this" => TokenType::THIS,
+    "trait" => TokenType::TRAIT,
     "true" => TokenType::TRUE,
     "var" => TokenType::VAR,
-    "while" => TokenType::WHILE
+    "while" => TokenType::WHILE,
+    "with" => TokenType::WITH,
+    "yield" => TokenType::YIELD
 );
 
+// shared by every lexer, so tokens from different sources (REPL lines, modules) never collide
+static NEXT_TOKEN_ID: AtomicUsize = AtomicUsize::new(1);
+
 // just iterator stuff, no token logic
 struct Scanner<'a> {
     iter: Peekable<Chars<'a>>,
@@ -136,26 +153,92 @@ impl<'a> Lexer<'a> {
             ')' => self.create_token(TokenType::RIGHT_PAREN, None),
             '{' => self.create_token(TokenType::LEFT_BRACE, None),
             '}' => self.create_token(TokenType::RIGHT_BRACE, None),
+            '[' => self.create_token(TokenType::LEFT_BRACKET, None),
+            ']' => self.create_token(TokenType::RIGHT_BRACKET, None),
             ',' => self.create_token(TokenType::COMMA, None),
-            '.' => self.create_token(TokenType::DOT, None),
-            '-' => self.create_token(TokenType::MINUS, None),
-            '+' => self.create_token(TokenType::PLUS, None),
+            '.' => {
+                if !self.iter.consume_if(|ch| ch == '.') {
+                    return self.create_token(TokenType::DOT, None);
+                }
+
+                if !self.iter.consume_if(|ch| ch == '.') {
+                    self.error("Unrecognized character .., did you mean '...'?");
+                    return None;
+                }
+                self.create_token(TokenType::ELLIPSIS, None)
+            }
             ';' => self.create_token(TokenType::SEMICOLON, None),
-            '*' => self.create_token(TokenType::STAR, None),
+            '%' => self.create_token(TokenType::PERCENT, None),
+            '&' => self.create_token(TokenType::AMPERSAND, None),
+            '|' => self.create_token(TokenType::PIPE, None),
+            '^' => self.create_token(TokenType::CARET, None),
+            '~' => {
+                // `//` already starts a comment, so integer division is spelled `~/`
+                let token_type = self.either('/', TokenType::TILDE_SLASH, TokenType::TILDE);
+                self.create_token(token_type, None)
+            }
+            ':' => {
+                let token_type = self.either(':', TokenType::COLON_COLON, TokenType::COLON);
+                self.create_token(token_type, None)
+            }
+            '?' => {
+                let token_type = if self.iter.consume_if(|ch| ch == '?') {
+                    TokenType::QUESTION_QUESTION
+                } else {
+                    self.either('.', TokenType::QUESTION_DOT, TokenType::QUESTION)
+                };
+                self.create_token(token_type, None)
+            }
+            '-' => {
+                let token_type = if self.iter.consume_if(|ch| ch == '-') {
+                    TokenType::MINUS_MINUS
+                } else {
+                    self.either('=', TokenType::MINUS_EQUAL, TokenType::MINUS)
+                };
+                self.create_token(token_type, None)
+            }
+            '+' => {
+                let token_type = if self.iter.consume_if(|ch| ch == '+') {
+                    TokenType::PLUS_PLUS
+                } else {
+                    self.either('=', TokenType::PLUS_EQUAL, TokenType::PLUS)
+                };
+                self.create_token(token_type, None)
+            }
+            '*' => {
+                let token_type = if self.iter.consume_if(|ch| ch == '*') {
+                    TokenType::STAR_STAR
+                } else {
+                    self.either('=', TokenType::STAR_EQUAL, TokenType::STAR)
+                };
+                self.create_token(token_type, None)
+            }
             '!' => {
                 let token_type = self.either('=', TokenType::BANG_EQUAL, TokenType::BANG);
                 self.create_token(token_type, None)
             }
             '=' => {
-                let token_type = self.either('=', TokenType::EQUAL_EQUAL, TokenType::EQUAL);
+                let token_type = if self.iter.consume_if(|ch| ch == '>') {
+                    TokenType::EQUAL_GREATER
+                } else {
+                    self.either('=', TokenType::EQUAL_EQUAL, TokenType::EQUAL)
+                };
                 self.create_token(token_type, None)
             }
             '<' => {
-                let token_type = self.either('=', TokenType::LESS_EQUAL, TokenType::LESS);
+                let token_type = if self.iter.consume_if(|ch| ch == '<') {
+                    TokenType::LESS_LESS
+                } else {
+                    self.either('=', TokenType::LESS_EQUAL, TokenType::LESS)
+                };
                 self.create_token(token_type, None)
             }
             '>' => {
-                let token_type = self.either('=', TokenType::GREATER_EQUAL, TokenType::GREATER);
+                let token_type = if self.iter.consume_if(|ch| ch == '>') {
+                    TokenType::GREATER_GREATER
+                } else {
+                    self.either('=', TokenType::GREATER_EQUAL, TokenType::GREATER)
+                };
                 self.create_token(token_type, None)
             }
             '/' => {
@@ -169,7 +252,8 @@ impl<'a> Lexer<'a> {
                     None
                 } else {
                     // division
-                    self.create_token(TokenType::SLASH, None)
+                    let token_type = self.either('=', TokenType::SLASH_EQUAL, TokenType::SLASH);
+                    self.create_token(token_type, None)
                 }
             }
             ' ' | '\r' | '\t' => None,
@@ -195,10 +279,10 @@ impl<'a> Lexer<'a> {
                 self.create_token(TokenType::STRING, Some(Literal::String(value)))
             }
             _ => {
-                if ch.is_digit(10) {
+                if ch.is_ascii_digit() {
                     // number literals
                     self.numbers(ch)
-                } else if ch.is_alphabetic() {
+                } else if ch.is_alphabetic() || ch == '_' {
                     // reserved words and identifiers
                     self.identifiers(ch)
                 } else {
@@ -214,6 +298,7 @@ impl<'a> Lexer<'a> {
             token_type,
             literal,
             line: self.iter.line,
+            id: NEXT_TOKEN_ID.fetch_add(1, Ordering::Relaxed),
         })
     }
 
@@ -264,19 +349,19 @@ impl<'a> Lexer<'a> {
         number.push_str(
             &self
                 .iter
-                .consume_while(|ch| ch.is_digit(10))
+                .consume_while(|ch| ch.is_ascii_digit())
                 .into_iter()
                 .collect::<String>(),
         );
 
         if self.iter.consume_if(|ch| ch == '.')
-            && self.iter.peek().map_or(false, |&ch| ch.is_digit(10))
+            && self.iter.peek().is_some_and(|&ch| ch.is_ascii_digit())
         {
             number.push('.');
             number.push_str(
                 &self
                     .iter
-                    .consume_while(|ch| ch.is_digit(10))
+                    .consume_while(|ch| ch.is_ascii_digit())
                     .into_iter()
                     .collect::<String>(),
             );
@@ -296,7 +381,7 @@ impl<'a> Lexer<'a> {
         identifier.push_str(
             &self
                 .iter
-                .consume_while(|ch| ch.is_alphanumeric())
+                .consume_while(|ch| ch.is_alphanumeric() || ch == '_')
                 .into_iter()
                 .collect::<String>(),
         );
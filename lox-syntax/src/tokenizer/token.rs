@@ -8,6 +8,8 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
     MINUS,
@@ -15,16 +17,38 @@ pub enum TokenType {
     SEMICOLON,
     SLASH,
     STAR,
+    COLON,
+    PERCENT,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    ELLIPSIS,
 
     // One or two character tokens.
     BANG,
     BANG_EQUAL,
+    COLON_COLON,
     EQUAL,
     EQUAL_EQUAL,
+    EQUAL_GREATER,
     GREATER,
     GREATER_EQUAL,
+    GREATER_GREATER,
     LESS,
     LESS_EQUAL,
+    LESS_LESS,
+    MINUS_EQUAL,
+    MINUS_MINUS,
+    PLUS_EQUAL,
+    PLUS_PLUS,
+    SLASH_EQUAL,
+    STAR_EQUAL,
+    STAR_STAR,
+    TILDE,
+    TILDE_SLASH,
+    QUESTION,
+    QUESTION_QUESTION,
+    QUESTION_DOT,
 
     // Literals.
     IDENTIFIER,
@@ -35,20 +59,30 @@ pub enum TokenType {
     AND,
     BREAK,
     CLASS,
+    CONST,
     ELSE,
+    ENUM,
+    EXPORT,
     FALSE,
     FUN,
     FOR,
     IF,
+    IMPORT,
+    LET,
+    MATCH,
     NIL,
     OR,
     PRINT,
     RETURN,
+    STATIC,
     SUPER,
     THIS,
+    TRAIT,
     TRUE,
     VAR,
     WHILE,
+    WITH,
+    YIELD,
 
     EOF,
     INVALID,
@@ -67,6 +101,8 @@ pub struct Token {
     pub token_type: TokenType,
     pub literal: Option<Literal>,
     pub line: usize,
+    // unique to each scanned token, so the same name in different places can be told apart
+    pub id: usize,
 }
 
 impl Display for TokenType {
@@ -76,6 +112,8 @@ impl Display for TokenType {
             TokenType::RIGHT_PAREN => write!(f, ")"),
             TokenType::LEFT_BRACE => write!(f, "{{"),
             TokenType::RIGHT_BRACE => write!(f, "}}"),
+            TokenType::LEFT_BRACKET => write!(f, "["),
+            TokenType::RIGHT_BRACKET => write!(f, "]"),
             TokenType::COMMA => write!(f, ","),
             TokenType::DOT => write!(f, "."),
             TokenType::MINUS => write!(f, "-"),
@@ -83,34 +121,66 @@ impl Display for TokenType {
             TokenType::SEMICOLON => write!(f, ";"),
             TokenType::SLASH => write!(f, "/"),
             TokenType::STAR => write!(f, "*"),
+            TokenType::COLON => write!(f, ":"),
+            TokenType::PERCENT => write!(f, "%"),
+            TokenType::AMPERSAND => write!(f, "&"),
+            TokenType::PIPE => write!(f, "|"),
+            TokenType::CARET => write!(f, "^"),
+            TokenType::ELLIPSIS => write!(f, "..."),
             TokenType::BANG => write!(f, "!"),
             TokenType::BANG_EQUAL => write!(f, "!="),
+            TokenType::COLON_COLON => write!(f, "::"),
             TokenType::EQUAL => write!(f, "="),
             TokenType::EQUAL_EQUAL => write!(f, "=="),
+            TokenType::EQUAL_GREATER => write!(f, "=>"),
             TokenType::GREATER => write!(f, ">"),
             TokenType::GREATER_EQUAL => write!(f, ">="),
+            TokenType::GREATER_GREATER => write!(f, ">>"),
             TokenType::LESS => write!(f, "<"),
             TokenType::LESS_EQUAL => write!(f, "<="),
+            TokenType::LESS_LESS => write!(f, "<<"),
+            TokenType::MINUS_EQUAL => write!(f, "-="),
+            TokenType::MINUS_MINUS => write!(f, "--"),
+            TokenType::PLUS_EQUAL => write!(f, "+="),
+            TokenType::PLUS_PLUS => write!(f, "++"),
+            TokenType::SLASH_EQUAL => write!(f, "/="),
+            TokenType::STAR_EQUAL => write!(f, "*="),
+            TokenType::STAR_STAR => write!(f, "**"),
+            TokenType::TILDE => write!(f, "~"),
+            TokenType::TILDE_SLASH => write!(f, "~/"),
+            TokenType::QUESTION => write!(f, "?"),
+            TokenType::QUESTION_QUESTION => write!(f, "??"),
+            TokenType::QUESTION_DOT => write!(f, "?."),
             TokenType::IDENTIFIER => write!(f, "Identifier"),
             TokenType::STRING => write!(f, "String"),
             TokenType::NUMBER => write!(f, "Number"),
             TokenType::AND => write!(f, "&&"),
             TokenType::BREAK => write!(f, "Break"),
             TokenType::CLASS => write!(f, "Class"),
+            TokenType::CONST => write!(f, "Const"),
             TokenType::ELSE => write!(f, "Else"),
+            TokenType::ENUM => write!(f, "Enum"),
+            TokenType::EXPORT => write!(f, "Export"),
             TokenType::FALSE => write!(f, "False"),
             TokenType::FUN => write!(f, "Function"),
             TokenType::FOR => write!(f, "For"),
             TokenType::IF => write!(f, "If"),
+            TokenType::IMPORT => write!(f, "Import"),
+            TokenType::LET => write!(f, "Let"),
+            TokenType::MATCH => write!(f, "Match"),
             TokenType::NIL => write!(f, "Nil"),
             TokenType::OR => write!(f, "Or"),
             TokenType::PRINT => write!(f, "Print"),
             TokenType::RETURN => write!(f, "Return"),
+            TokenType::STATIC => write!(f, "Static"),
             TokenType::SUPER => write!(f, "Super"),
             TokenType::THIS => write!(f, "This"),
+            TokenType::TRAIT => write!(f, "Trait"),
             TokenType::TRUE => write!(f, "True"),
             TokenType::VAR => write!(f, "Var"),
             TokenType::WHILE => write!(f, "While"),
+            TokenType::WITH => write!(f, "With"),
+            TokenType::YIELD => write!(f, "Yield"),
             TokenType::EOF => write!(f, "Eof"),
             TokenType::INVALID => write!(f, "Invalid"),
         }
@@ -128,28 +198,28 @@ impl Display for Literal {
     }
 }
 
-impl Into<TokenType> for &Token {
-    fn into(self) -> TokenType {
-        self.token_type
+impl From<&Token> for TokenType {
+    fn from(val: &Token) -> Self {
+        val.token_type
     }
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.token_type == TokenType::THIS {
-            return "this".to_string();
+            return write!(f, "this");
         }
 
         if self.token_type == TokenType::SUPER {
-            return "super".to_string();
+            return write!(f, "super");
         }
 
         match &self.literal {
-            Some(Literal::String(s)) => s.clone(),
-            Some(Literal::Number(n)) => n.to_string(),
-            Some(Literal::Bool(b)) => b.to_string(),
-            Some(Literal::Null) => "null".to_string(),
-            None => "".to_string(),
+            Some(Literal::String(s)) => write!(f, "{}", s),
+            Some(Literal::Number(n)) => write!(f, "{}", n),
+            Some(Literal::Bool(b)) => write!(f, "{}", b),
+            Some(Literal::Null) => write!(f, "null"),
+            None => Ok(()),
         }
     }
 }
@@ -1,32 +1,72 @@
 use error::{report, Result};
-use lox_interpreter::{Interpreter, Resolver};
-use lox_std::set_stdlib;
+use lox_interpreter::{Engine, Interpreter, Limits, Resolver, TypeChecker};
+use lox_std::{set_stdlib, set_stdlib_with, Capabilities};
 use std::{
     cell::RefCell,
     fs,
     io::{self, BufRead, Write},
     process,
     rc::Rc,
+    thread,
 };
 
 use log::debug;
 
 mod error;
 
-use lox_syntax::{parse_program, Lexer, TreePrinter};
+use lox_syntax::{parse_program, Lexer, Stmt};
 
-fn run_file(path: String) -> Result<()> {
-    let content = fs::read_to_string(path)?;
+// Scripts run on a thread with a large stack, so deep recursion stops at `MAX_DEPTH` calls with an
+// error instead of overflowing it. Only the pages used are committed.
+const STACK_SIZE: usize = 1024 * 1024 * 1024;
+const MAX_DEPTH: usize = 10_000;
+
+fn limits() -> Limits {
+    Limits {
+        depth: Some(MAX_DEPTH),
+        ..Limits::default()
+    }
+}
+
+// `--sandbox` runs the script without the natives reaching the host
+fn run_file(path: String, capabilities: Capabilities) -> Result<()> {
+    let mut engine = Engine::builder()
+        .module(|interpreter| set_stdlib_with(interpreter, capabilities))
+        .limits(limits())
+        .build();
+    if let Err(diagnostics) = engine.run_file(&path) {
+        engine.report(&diagnostics);
+        process::exit(diagnostics.exit_code());
+    }
+
+    Ok(())
+}
+
+// `lox check [--types] script` reports the errors of a script without running it
+fn check_file(path: String, types: bool) -> Result<()> {
+    let content = fs::read_to_string(&path)?;
     let interpreter = Rc::new(RefCell::new(Interpreter::new()));
-    set_stdlib(interpreter.clone());
-    let had_runtime_error = run(&content, interpreter);
+    interpreter.borrow_mut().set_script_path(&path)?;
+    set_stdlib(&mut interpreter.borrow_mut());
+
+    let Some(statements) = parse(&content) else {
+        process::exit(65);
+    };
 
-    if error::had_error() {
+    let mut resolver = Resolver::new(interpreter);
+    if let Err(error) = resolver.resolve_stmts(&statements) {
+        eprintln!("{}", error);
         process::exit(65);
     }
 
-    if had_runtime_error {
-        process::exit(70);
+    if types {
+        let errors = TypeChecker::new().check(&statements);
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        if !errors.is_empty() {
+            process::exit(65);
+        }
     }
 
     Ok(())
@@ -37,7 +77,7 @@ fn run_prompt() -> Result<()> {
     let mut stdout = io::stdout();
     let mut handle = stdin.lock();
 
-    let interpreter = Rc::new(RefCell::new(Interpreter::new()));
+    let mut engine = Engine::builder().limits(limits()).build();
 
     loop {
         print!("> ");
@@ -51,44 +91,31 @@ fn run_prompt() -> Result<()> {
             break;
         }
 
-        run(line.trim(), interpreter.clone());
-        error::reset();
+        debug!("Running: \n{}\n", line);
+        if let Err(diagnostics) = engine.eval(line.trim()) {
+            engine.report(&diagnostics);
+        }
     }
 
     Ok(())
 }
 
-fn run(code: &str, interpreter: Rc<RefCell<Interpreter>>) -> bool {
-    debug!("Running: \n{}\n", code);
-
+// lexes and parses `code`, reporting the error when there is one
+fn parse(code: &str) -> Option<Vec<Stmt>> {
     let mut scanner = Lexer::new(code);
     let tokens = match scanner.scan_tokens() {
         Ok(tok) => tok,
         Err(e) => {
             report(Box::new(e));
-            return false;
+            return None;
         }
     };
 
-    let statements = match parse_program(&tokens) {
-        Ok(stmts) => stmts,
+    match parse_program(&tokens) {
+        Ok(stmts) => Some(stmts),
         Err(e) => {
             report(Box::new(e));
-            return false;
-        }
-    };
-
-    let mut resolver = Resolver::new(interpreter.clone());
-    if let Err(error) = resolver.resolve_stmts(&statements) {
-        eprintln!("{}", error);
-        return true;
-    }
-
-    match interpreter.borrow_mut().interpret(&statements) {
-        Ok(()) => false,
-        Err(error) => {
-            eprintln!("{}", error);
-            true
+            None
         }
     }
 }
@@ -96,13 +123,24 @@ fn run(code: &str, interpreter: Rc<RefCell<Interpreter>>) -> bool {
 fn main() -> Result<()> {
     env_logger::init();
 
-    let mut arguments = std::env::args();
-    if arguments.len() > 2 {
-        println!("Usage: jlox [script]");
-    } else if arguments.len() == 2 {
-        run_file(arguments.nth(1).expect("no script argument"))?
-    } else {
-        run_prompt()?
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(arguments).map_err(|e| e.to_string()))?;
+    match runner.join() {
+        Ok(result) => Ok(result?),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+fn run(arguments: Vec<String>) -> Result<()> {
+    match arguments.as_slice() {
+        [command, script] if command == "check" => check_file(script.clone(), false)?,
+        [command, flag, script] if command == "check" && flag == "--types" => check_file(script.clone(), true)?,
+        [flag, script] if flag == "--sandbox" => run_file(script.clone(), Capabilities::locked_down())?,
+        [script] => run_file(script.clone(), Capabilities::all())?,
+        [] => run_prompt()?,
+        _ => println!("Usage: jlox [[--sandbox] script] | jlox check [--types] script"),
     }
 
     Ok(())